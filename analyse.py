@@ -1,47 +1,178 @@
 import csv
+import math
 import argparse
 import matplotlib.pyplot as plt
 
+# two sided 95% quantile of the standard normal distribution
+Z_95 = 1.96
+# most players a single deck can deal to, the number of finishing position columns
+MAX_PLAYERS = 23
+HAND_CATEGORIES = ["High Card", "Pair", "Two Pair", "Trips", "Straight",
+                   "Flush", "Full House", "Quads", "Straight Flush", "Royal Flush"]
+# newer files start with "# key: value" metadata lines, followed by a header row
+METADATA_PREFIX = "#"
+# column names of the counts, the same order as the values of legacy rows
+CATEGORY_COLUMNS = [name.lower().replace(" ", "_")
+                    for name in HAND_CATEGORIES]
+STREETS = ["flop", "turn"]
+COLUMNS = (["hands", "wins", "ties", "pot_share"]
+           + [f"showdown_{category}" for category in CATEGORY_COLUMNS]
+           + [f"win_{category}" for category in CATEGORY_COLUMNS]
+           + [f"loss_{own}_to_{winner}" for own in CATEGORY_COLUMNS for winner in CATEGORY_COLUMNS]
+           + [f"finish_{position}" for position in range(1, MAX_PLAYERS + 1)]
+           + [f"{street}_{category}" for street in STREETS for category in CATEGORY_COLUMNS]
+           + [f"{street}_leads" for street in STREETS]
+           + ["flop_leads_won"])
+
 
 def plot_data(stats, total_stats, num_players):
     hand_names = []
     win_rates = []
+    errors = []
     iterations = total_stats[0]
     num_hands = total_stats[1]
     for hand in stats:
         hand_names.append(hand[0])
         win_rates.append(hand[1])
-    plt.barh(hand_names[::-1], win_rates[::-1])
+        errors.append(Z_95 * hand[2])
+    plt.barh(hand_names[::-1], win_rates[::-1], xerr=errors[::-1])
     plt.xlabel("Win Rate")
     plt.title(
         f"Win Rates for Each Hand with {num_players} Players After {iterations} Iterations. Total Hands: {num_hands}")
     plt.show()
 
 
+def read_counts(row, prefix, names):
+    columns = [f"{prefix}{name}" for name in names]
+    if not all(column in row for column in columns):
+        return []
+    return [int(row[column]) for column in columns]
+
+
+def read_hand(hand_name, row):
+    num_times_played = int(row["hands"])
+    # legacy rows only have hands and wins, chopped pots counted as wins
+    pot_share = float(row["pot_share"]) if "pot_share" in row else int(
+        row["wins"])
+
+    win_ratio = 0 if num_times_played == 0 else pot_share/num_times_played
+    # pot shares are between 0 and 1, so the binomial error is an upper bound
+    std_error = 0 if num_times_played == 0 else math.sqrt(
+        win_ratio * (1 - win_ratio) / num_times_played)
+    # showdown and win counts per hand category, missing from older rows
+    showdown_categories = read_counts(row, "showdown_", CATEGORY_COLUMNS)
+    win_categories = read_counts(row, "win_", CATEGORY_COLUMNS)
+    # lost pots by own category and then by the winner's category
+    loss_matrix = [read_counts(row, f"loss_{own}_to_", CATEGORY_COLUMNS)
+                   for own in CATEGORY_COLUMNS]
+    if not all(loss_matrix):
+        loss_matrix = []
+    # showdowns finished 1st, 2nd, ... with tied hands sharing a position
+    finishing_positions = read_counts(
+        row, "finish_", range(1, MAX_PLAYERS + 1))
+    # hands with the counts above, legacy rows only count hands and wins. Rows written before
+    # this was stored counted every showdown by category.
+    detailed_hands = int(row["detailed_hands"]) if "detailed_hands" in row else sum(
+        showdown_categories)
+    # how often each street was led and how often a flop lead won, out of the hands dealt
+    # while streets were tracked
+    street_stats = {}
+    if "flop_leads_won" in row:
+        street_stats = {name: int(row[name])
+                        for name in ["flop_leads", "turn_leads", "flop_leads_won"]}
+        for street in STREETS:
+            street_stats[f"{street}_hands"] = sum(
+                read_counts(row, f"{street}_", CATEGORY_COLUMNS))
+    return (hand_name, win_ratio, std_error, showdown_categories,
+            win_categories, loss_matrix, finishing_positions,
+            detailed_hands, street_stats)
+
+
 def read_file(file_name):
     with open(file_name) as csv_file:
-        csv_reader = csv.reader(csv_file)
-        line_count = 0
-        hands = []
-        total_stats = []
+        lines = csv_file.read().splitlines()
+    hands = []
+    if lines and lines[0].startswith(METADATA_PREFIX):
+        metadata = dict(line.lstrip(METADATA_PREFIX).split(":", 1)
+                        for line in lines if line.startswith(METADATA_PREFIX))
+        metadata = {key.strip(): value.strip()
+                    for (key, value) in metadata.items()}
+        total_stats = [metadata["iterations"], metadata["unique_hands"]]
+        rows = csv.DictReader(
+            line for line in lines if not line.startswith(METADATA_PREFIX))
+        for row in rows:
+            hands.append(read_hand(row["hand"], row))
+    else:
+        # legacy files hold every row in one quoted field, the totals first
+        csv_reader = csv.reader(lines)
+        total_stats = next(csv_reader)[0].split(",")[:2]
         for row in csv_reader:
             line_contents = row[0].split(",")
-            if line_count == 0:
-                iterations = line_contents[0]
-                num_hands = line_contents[1]
-                total_stats.append(iterations)
-                total_stats.append(num_hands)
+            if len(line_contents) == 3:
+                values = {"hands": line_contents[1],
+                          "wins": line_contents[2]}
             else:
-                hand_name = line_contents[0]
-                num_times_played = int(line_contents[1])
-                num_times_won = int(line_contents[2])
+                values = dict(zip(COLUMNS, line_contents[1:]))
+            hands.append(read_hand(line_contents[0], values))
+
+    return (sorted(hands, key=lambda x: x[1], reverse=True), total_stats)
+
+
+def format_categories(category_counts):
+    total = sum(category_counts)
+    if total == 0:
+        return "no data"
+    shares = sorted(zip(HAND_CATEGORIES, category_counts),
+                    key=lambda x: x[1], reverse=True)
+    return ", ".join(f"{name} {count / total:.1%}" for (name, count) in shares if count > 0)
+
 
-                win_ratio = 0 if num_times_played == 0 else num_times_won/num_times_played
-                hands.append((hand_name, win_ratio))
+def print_categories(stats):
+    for hand in stats:
+        print(hand[0])
+        print(f"  wins with: {format_categories(hand[4])}")
+        print(f"  showdowns with: {format_categories(hand[3])}")
 
-            line_count += 1
 
-        return (sorted(hands, key=lambda x: x[1], reverse=True), total_stats)
+def print_losses(stats, num_shown=5):
+    for hand in stats:
+        losses = [(own, winner, count)
+                  for (own, row) in enumerate(hand[5]) for (winner, count) in enumerate(row)]
+        total = sum(count for (_, _, count) in losses)
+        print(hand[0])
+        if total == 0:
+            print("  no data")
+            continue
+        for (own, winner, count) in sorted(losses, key=lambda x: x[2], reverse=True)[:num_shown]:
+            print(
+                f"  lost with {HAND_CATEGORIES[own]} to {HAND_CATEGORIES[winner]}: {count / total:.1%}")
+
+
+def print_positions(stats, num_players):
+    for hand in stats:
+        finishing_positions = hand[6][:num_players]
+        total = sum(finishing_positions)
+        if total == 0:
+            print(f"{hand[0]}: no data")
+            continue
+        shares = " ".join(f"{count / total:6.1%}" for count in finishing_positions)
+        print(f"{hand[0]:<28} {shares}")
+
+
+def print_streets(stats):
+    print(f"{'Hand':<28} {'Leads flop':>10} {'Leads turn':>10} {'Wins river':>10} {'Flop lead holds':>15}")
+    for hand in stats:
+        detailed_hands = hand[7]
+        street_stats = hand[8]
+        if (not street_stats or detailed_hands == 0 or street_stats["flop_hands"] == 0
+                or street_stats["turn_hands"] == 0):
+            print(f"{hand[0]:<28} no data")
+            continue
+        flop_leads = street_stats["flop_leads"]
+        lead_holds = 0 if flop_leads == 0 else street_stats["flop_leads_won"] / flop_leads
+        print(f"{hand[0]:<28} {flop_leads / street_stats['flop_hands']:>10.1%} "
+              f"{street_stats['turn_leads'] / street_stats['turn_hands']:>10.1%} "
+              f"{sum(hand[4]) / detailed_hands:>10.1%} {lead_holds:>15.1%}")
 
 
 def get_args():
@@ -50,6 +181,14 @@ def get_args():
                         help='Number of players at a table (default: 8)')
     parser.add_argument('-v', '--verbose', action='store_true',
                         help='Increase detail in hand outputs.')
+    parser.add_argument('-c', '--categories', action='store_true',
+                        help='Print which made hands each hand wins and reaches showdown with.')
+    parser.add_argument('-l', '--losses', action='store_true',
+                        help='Print what each hand most often loses with and to.')
+    parser.add_argument('-p', '--positions', action='store_true',
+                        help='Print how often each hand finishes 1st, 2nd, ... at showdown.')
+    parser.add_argument('-s', '--streets', action='store_true',
+                        help='Print how often each hand leads on the flop and turn and holds the flop lead.')
 
     args = parser.parse_args()
 
@@ -65,7 +204,16 @@ if __name__ == "__main__":
     (stats, total_stats) = read_file(
         f"output_{num_players}_players{verbose_s}.csv")
 
-    for hand in stats:
-        print(hand)
+    if args.categories:
+        print_categories(stats)
+    elif args.losses:
+        print_losses(stats)
+    elif args.positions:
+        print_positions(stats, num_players)
+    elif args.streets:
+        print_streets(stats)
+    else:
+        for hand in stats:
+            print(hand[:3])
 
     plot_data(stats, total_stats, num_players)
@@ -1,7 +0,0 @@
-#!/bin/bash
-
-if [ ! -d "./output_backups" ]; then
-    mkdir ./output_backups
-fi
-
-cp *.csv ./output_backups
\ No newline at end of file
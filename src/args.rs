@@ -1,13 +1,118 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+use super::card::Card;
+use super::dealer::MAX_PLAYERS;
+use super::hand_analyser::Evaluator;
+use super::range::{Range, RangeParseError};
 
 const DEFAULT_PLAYER_NUM: u32 = 8;
 const DEFAULT_NUM_THOUSAND_ITERATIONS: u32 = 1;
+const DEFAULT_NUM_THREADS: u32 = 1;
+const DEFAULT_NUM_THOUSAND_BENCH_HANDS: u32 = 1000;
+const NUM_HOLE_CARDS: usize = 2;
+const MIN_EQUITY_HANDS: usize = 2;
+// enough for every heads-up preflop board
+const DEFAULT_EXACT_THRESHOLD: u64 = 2_000_000;
+const MIN_BOARD_CARDS: usize = 3;
+const MAX_BOARD_CARDS: usize = 5;
+const MIN_MERGE_FILES: usize = 2;
+const MIN_PLAYERS: i64 = 2;
+
+fn parse_hole_cards(hole_cards_str: &str) -> Result<Vec<Card>, String> {
+    let hole_cards = Card::parse_cards(hole_cards_str).map_err(|err| err.to_string())?;
+    if hole_cards.len() != NUM_HOLE_CARDS {
+        return Err(format!("expected {} hole cards", NUM_HOLE_CARDS));
+    }
+    if hole_cards[0] == hole_cards[1] {
+        return Err("hole cards must be different".to_string());
+    }
+    return Ok(hole_cards);
+}
+
+fn parse_target_stderr(target_str: &str) -> Result<f64, String> {
+    let target_stderr = target_str.parse::<f64>().map_err(|err| err.to_string())?;
+    if !(target_stderr > 0.0 && target_stderr < 1.0) {
+        return Err("expected a standard error above 0 and below 1".to_string());
+    }
+    return Ok(target_stderr);
+}
+
+fn parse_range(range_str: &str) -> Result<Range, String> {
+    return range_str
+        .parse()
+        .map_err(|err: RangeParseError| err.to_string());
+}
+
+fn parse_board(board_str: &str) -> Result<Vec<Card>, String> {
+    let board = Card::parse_cards(board_str).map_err(|err| err.to_string())?;
+    if board.len() < MIN_BOARD_CARDS || board.len() > MAX_BOARD_CARDS {
+        return Err(format!(
+            "expected between {} and {} board cards",
+            MIN_BOARD_CARDS, MAX_BOARD_CARDS
+        ));
+    }
+    return Ok(board);
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Measure how many seven card hands per second each evaluator can rank.
+    Bench {
+        /// Number of thousands of hands to evaluate.
+        #[arg(default_value_t = DEFAULT_NUM_THOUSAND_BENCH_HANDS)]
+        num_thousand_hands: u32,
+    },
+    /// Report the win, tie and equity percentages of specific hands against each other.
+    Equity {
+        /// Two or more hands or ranges, e.g. "AsKs" "QhQd" or "QQ+, AKs" "JJ-22, A5s-A2s:0.5".
+        /// The first one is broken down by combo, at most one per player a deck can deal to.
+        #[arg(required = true, num_args = MIN_EQUITY_HANDS..=MAX_PLAYERS as usize, value_parser = parse_range)]
+        ranges: Vec<Range>,
+
+        /// Known community cards, the flop (e.g. "Qs7d2c") optionally with the turn and river.
+        #[arg(long, value_parser = parse_board)]
+        board: Option<::std::vec::Vec<Card>>,
+
+        /// Cards known to be out of play (folded or exposed), e.g. "9c9h".
+        #[arg(long, value_parser = Card::parse_cards)]
+        dead: Option<::std::vec::Vec<Card>>,
+
+        /// Number of thousands of iteratons to run.
+        #[arg(short, long, default_value_t = DEFAULT_NUM_THOUSAND_ITERATIONS)]
+        num_thousand_iterations: u32,
+
+        /// Enumerate every remaining board instead of sampling when there are at most this many.
+        #[arg(short = 'x', long, default_value_t = DEFAULT_EXACT_THRESHOLD)]
+        exact_threshold: u64,
+    },
+    /// Combine the result files of several runs, e.g. from different machines, into one.
+    Merge {
+        /// Number of players per table every file must hold.
+        #[arg(value_parser = clap::value_parser!(u32).range(MIN_PLAYERS..=MAX_PLAYERS as i64))]
+        num_players: u32,
+
+        /// Result files to merge, all in the same format version.
+        #[arg(required = true, num_args = MIN_MERGE_FILES..)]
+        files: Vec<String>,
+
+        /// File to write the merged results to, it may be one of the merged files.
+        #[arg(short, long)]
+        output: String,
+
+        /// Merge files holding all possible two card combinations.
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
+}
 
 #[derive(Debug, Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Number of players per table.
-    #[arg(default_value_t = DEFAULT_PLAYER_NUM)]
+    #[arg(default_value_t = DEFAULT_PLAYER_NUM, value_parser = clap::value_parser!(u32).range(MIN_PLAYERS..=MAX_PLAYERS as i64))]
     pub num_players: u32,
 
     /// Number of thousands of iteratons to run.
@@ -18,7 +123,60 @@ pub struct Args {
     #[arg(short, long, default_value_t = false)]
     pub verbose: bool,
 
+    /// Also record every hand's made hand and whether it led on the flop and the turn, which
+    /// makes runs slower.
+    #[arg(long, default_value_t = false, conflicts_with = "hero")]
+    pub streets: bool,
+
+    /// Number of threads to run simulations on.
+    #[arg(short, long, global = true, default_value_t = DEFAULT_NUM_THREADS, value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: u32,
+
+    /// How hands are evaluated at showdown.
+    #[arg(short, long, global = true, value_enum, default_value_t = Evaluator::Bitmask)]
+    pub evaluator: Evaluator,
+
+    /// Seed for the random number generator, the same seed, player count and number of
+    /// iterations always give the same results. A random seed is used if not given.
+    #[arg(short, long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Ignore the number of iterations and run until the standard error of every hand's win rate
+    /// is below this, e.g. 0.001.
+    #[arg(long, conflicts_with = "hero", value_parser = parse_target_stderr)]
+    pub target_stderr: Option<f64>,
+
+    /// Stop running for the target standard error after this many thousands of iterations.
+    #[arg(long, requires = "target_stderr", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_thousand_iterations: Option<u32>,
+
+    /// Stop running for the target standard error after this many seconds.
+    #[arg(long, requires = "target_stderr")]
+    pub max_seconds: Option<f64>,
+
+    /// Print every hand ranked by win rate, with its standard error and 95% interval.
+    #[arg(short, long, default_value_t = false, conflicts_with = "hero")]
+    pub ranking: bool,
+
     /// Log info.
-    #[arg(short, long, default_value_t = false)]
+    #[arg(short, long, global = true, default_value_t = false)]
     pub debug: bool,
+
+    /// Fix your hole cards (e.g. "AhKd") and report their equity against random opponents.
+    #[arg(long, value_parser = parse_hole_cards)]
+    pub hero: Option<::std::vec::Vec<Card>>,
+
+    /// Range of the next opponent in hero mode, e.g. "QQ+, AKs, A5s-A2s, 76s:0.5". Repeat for
+    /// more opponents, any opponents left over get random hands.
+    #[arg(long = "villain", value_parser = parse_range, requires = "hero")]
+    pub villains: Vec<Range>,
+
+    /// Known community cards for hero mode, the flop (e.g. "Qs7d2c") optionally with the turn and
+    /// river.
+    #[arg(long, value_parser = parse_board, requires = "hero")]
+    pub board: Option<::std::vec::Vec<Card>>,
+
+    /// Cards known to be out of play in hero mode (folded or exposed), e.g. "9c9h".
+    #[arg(long, value_parser = Card::parse_cards, requires = "hero")]
+    pub dead: Option<::std::vec::Vec<Card>>,
 }
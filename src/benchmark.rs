@@ -0,0 +1,58 @@
+use super::card::Card;
+use super::dealer::Dealer;
+use super::deck::Deck;
+use super::hand_analyser::{Evaluator, HandRank};
+
+use std::hint::black_box;
+use std::time::Instant;
+
+const NUM_CARDS_PER_PLAYER: usize = 7;
+const EVALUATORS: [Evaluator; 2] = [Evaluator::Grouping, Evaluator::Bitmask];
+// hands are dealt and ranked a chunk at a time so memory stays flat however many are asked for
+const HANDS_PER_CHUNK: u64 = 100_000;
+
+/// Deals `num_hands` random seven card hands and times how fast each evaluator ranks them.
+pub fn run_benchmark(num_hands: u64, seed: u64) {
+    let mut deck = Deck::new();
+    let mut rng = Dealer::seeded_rng(seed, 0);
+    let mut seconds = [0.0; EVALUATORS.len()];
+    let mut num_mismatches: u64 = 0;
+
+    let mut num_hands_left = num_hands;
+    while num_hands_left > 0 {
+        let num_chunk_hands = num_hands_left.min(HANDS_PER_CHUNK);
+        num_hands_left -= num_chunk_hands;
+        let hands = (0..num_chunk_hands)
+            .map(|_| deck.deal(NUM_CARDS_PER_PLAYER, &mut rng).to_vec())
+            .collect::<Vec<Vec<Card>>>();
+
+        let chunk_ranks = EVALUATORS.map(|evaluator| {
+            let start = Instant::now();
+            let ranks = hands
+                .iter()
+                .map(|cards| black_box(evaluator.evaluate(black_box(cards))))
+                .collect::<Vec<HandRank>>();
+            (ranks, start.elapsed().as_secs_f64())
+        });
+        for (evaluator_seconds, (_, chunk_seconds)) in seconds.iter_mut().zip(&chunk_ranks) {
+            *evaluator_seconds += chunk_seconds;
+        }
+        num_mismatches += chunk_ranks[0]
+            .0
+            .iter()
+            .zip(&chunk_ranks[1].0)
+            .filter(|(grouping_rank, bitmask_rank)| grouping_rank != bitmask_rank)
+            .count() as u64;
+    }
+
+    for (evaluator, seconds) in EVALUATORS.iter().zip(seconds) {
+        println!(
+            "{:?}: {} hands in {:.3}s, {:.0} hands per second",
+            evaluator,
+            num_hands,
+            seconds,
+            num_hands as f64 / seconds
+        );
+    }
+    println!("Hands ranked differently: {}", num_mismatches);
+}
@@ -0,0 +1,183 @@
+use super::card::{Card, NUM_SUITS};
+use super::hand_analyser::HandRank;
+
+const NUM_CARDS_IN_HAND: usize = 5;
+const NUM_CARD_NUMBERS: usize = 13;
+const LOWEST_CARD_VALUE: u32 = 2;
+const ACE_VALUE: u32 = 14;
+const FIVE_VALUE: u32 = 5;
+const STRAIGHT_MASK: u16 = 0b1_1111;
+const WHEEL_MASK: u16 = 0b1_0000_0000_1111;
+
+const ROYAL_FLUSH_VALUE: u32 = 10;
+const STRAIGHT_FLUSH_VALUE: u32 = 9;
+const QUADS_VALUE: u32 = 8;
+const FULL_HOUSE_VALUE: u32 = 7;
+const FLUSH_VALUE: u32 = 6;
+const STRAIGHT_VALUE: u32 = 5;
+const TRIPS_VALUE: u32 = 4;
+const TWO_PAIR_VALUE: u32 = 3;
+const PAIR_VALUE: u32 = 2;
+const HIGH_CARD_VALUE: u32 = 1;
+
+fn card_number_bit(card_value: u32) -> u16 {
+    return 1 << (card_value - LOWEST_CARD_VALUE);
+}
+
+/// Returns the value of the highest card of the best straight in `number_mask`.
+fn get_straight_high_card(number_mask: u16) -> Option<u32> {
+    for low_bit in (0..=NUM_CARD_NUMBERS - NUM_CARDS_IN_HAND).rev() {
+        let straight = STRAIGHT_MASK << low_bit;
+        if number_mask & straight == straight {
+            return Some(low_bit as u32 + LOWEST_CARD_VALUE + NUM_CARDS_IN_HAND as u32 - 1);
+        }
+    }
+    if number_mask & WHEEL_MASK == WHEEL_MASK {
+        return Some(FIVE_VALUE);
+    }
+    return None;
+}
+
+/// The card numbers of a straight, the ace of a wheel is played last.
+fn get_straight_numbers(high_card: u32) -> [u32; NUM_CARDS_IN_HAND] {
+    if high_card == FIVE_VALUE {
+        return [5, 4, 3, 2, ACE_VALUE];
+    }
+    return [
+        high_card,
+        high_card - 1,
+        high_card - 2,
+        high_card - 3,
+        high_card - 4,
+    ];
+}
+
+fn get_highest_value(number_mask: u16) -> Option<u32> {
+    if number_mask == 0 {
+        return None;
+    }
+    return Some(u16::BITS - 1 - number_mask.leading_zeros() + LOWEST_CARD_VALUE);
+}
+
+/// Fills `card_numbers` from index `start` with the highest card numbers in `number_mask`.
+fn fill_with_highest(card_numbers: &mut [u32; NUM_CARDS_IN_HAND], start: usize, number_mask: u16) {
+    let mut remaining_mask = number_mask;
+    for card_number in card_numbers.iter_mut().skip(start) {
+        if let Some(card_value) = get_highest_value(remaining_mask) {
+            *card_number = card_value;
+            remaining_mask &= !card_number_bit(card_value);
+        }
+    }
+}
+
+/// Evaluates five to seven cards with one bitmask of card numbers per suit instead of grouping
+/// cards into maps. Gives exactly the same `HandRank` as `Evaluator::Grouping`.
+pub fn evaluate(cards: &[Card]) -> HandRank {
+    let mut suit_masks = [0u16; NUM_SUITS as usize];
+    let mut number_counts = [0u8; NUM_CARD_NUMBERS];
+    let mut number_mask = 0u16;
+    for card in cards {
+        let card_value = card.number.get_value();
+        suit_masks[card.suit.get_value() as usize] |= card_number_bit(card_value);
+        number_counts[(card_value - LOWEST_CARD_VALUE) as usize] += 1;
+        number_mask |= card_number_bit(card_value);
+    }
+
+    let flush_mask = suit_masks
+        .into_iter()
+        .find(|suit_mask| suit_mask.count_ones() as usize >= NUM_CARDS_IN_HAND);
+    if let Some(straight_flush_high_card) = flush_mask.and_then(get_straight_high_card) {
+        let hand_value = match straight_flush_high_card {
+            ACE_VALUE => ROYAL_FLUSH_VALUE,
+            _ => STRAIGHT_FLUSH_VALUE,
+        };
+        return HandRank::from_card_numbers(
+            hand_value,
+            &get_straight_numbers(straight_flush_high_card),
+        );
+    }
+
+    let mut quads_mask = 0u16;
+    let mut trips_mask = 0u16;
+    let mut pairs_mask = 0u16;
+    for (bit, count) in number_counts.iter().enumerate() {
+        match count {
+            4 => quads_mask |= 1 << bit,
+            3 => trips_mask |= 1 << bit,
+            2 => pairs_mask |= 1 << bit,
+            _ => {}
+        }
+    }
+
+    let mut card_numbers = [0; NUM_CARDS_IN_HAND];
+
+    if let Some(quads_value) = get_highest_value(quads_mask) {
+        card_numbers[..4].fill(quads_value);
+        fill_with_highest(
+            &mut card_numbers,
+            4,
+            number_mask & !card_number_bit(quads_value),
+        );
+        return HandRank::from_card_numbers(QUADS_VALUE, &card_numbers);
+    }
+
+    let trips_value = get_highest_value(trips_mask);
+    if let Some(trips_value) = trips_value {
+        // a second set of trips can be played as the pair
+        let pair_mask = (trips_mask | pairs_mask) & !card_number_bit(trips_value);
+        if let Some(pair_value) = get_highest_value(pair_mask) {
+            card_numbers = [
+                trips_value,
+                trips_value,
+                trips_value,
+                pair_value,
+                pair_value,
+            ];
+            return HandRank::from_card_numbers(FULL_HOUSE_VALUE, &card_numbers);
+        }
+    }
+
+    if let Some(suit_mask) = flush_mask {
+        fill_with_highest(&mut card_numbers, 0, suit_mask);
+        return HandRank::from_card_numbers(FLUSH_VALUE, &card_numbers);
+    }
+
+    if let Some(straight_high_card) = get_straight_high_card(number_mask) {
+        return HandRank::from_card_numbers(
+            STRAIGHT_VALUE,
+            &get_straight_numbers(straight_high_card),
+        );
+    }
+
+    if let Some(trips_value) = trips_value {
+        card_numbers[..3].fill(trips_value);
+        fill_with_highest(
+            &mut card_numbers,
+            3,
+            number_mask & !card_number_bit(trips_value),
+        );
+        return HandRank::from_card_numbers(TRIPS_VALUE, &card_numbers);
+    }
+
+    if let Some(first_pair) = get_highest_value(pairs_mask) {
+        let remaining_pairs_mask = pairs_mask & !card_number_bit(first_pair);
+        if let Some(second_pair) = get_highest_value(remaining_pairs_mask) {
+            card_numbers[..4].copy_from_slice(&[first_pair, first_pair, second_pair, second_pair]);
+            let kicker_mask =
+                number_mask & !card_number_bit(first_pair) & !card_number_bit(second_pair);
+            fill_with_highest(&mut card_numbers, 4, kicker_mask);
+            return HandRank::from_card_numbers(TWO_PAIR_VALUE, &card_numbers);
+        }
+
+        card_numbers[..2].fill(first_pair);
+        fill_with_highest(
+            &mut card_numbers,
+            2,
+            number_mask & !card_number_bit(first_pair),
+        );
+        return HandRank::from_card_numbers(PAIR_VALUE, &card_numbers);
+    }
+
+    fill_with_highest(&mut card_numbers, 0, number_mask);
+    return HandRank::from_card_numbers(HIGH_CARD_VALUE, &card_numbers);
+}
@@ -1,8 +1,54 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
 pub const NUM_SUITS: u32 = 4;
 pub const NUM_CARD_NUMBERS: u32 = 14;
 pub const TWO: u32 = 2;
+pub const NUM_STARTING_HANDS: usize = 169;
+pub const NUM_HOLE_CARD_COMBOS: usize = 1326;
+const NUM_DIFFERENT_CARD_NUMBERS: usize = 13;
+
+const CARD_NOTATION_LEN: usize = 2;
+const SUITED_CHAR: char = 's';
+const OFF_SUIT_CHAR: char = 'o';
+
+#[derive(Debug, PartialEq)]
+pub enum CardParseError {
+    UnknownCardNumber(char),
+    UnknownSuit(char),
+    WrongLength(String),
+    NotAStartingHand(String),
+}
+
+impl fmt::Display for CardParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownCardNumber(character) => write!(
+                f,
+                "\"{}\" is not a card number, expected one of 23456789TJQKA",
+                character
+            ),
+            Self::UnknownSuit(character) => {
+                write!(f, "\"{}\" is not a suit, expected one of dshc", character)
+            }
+            Self::WrongLength(cards_str) => write!(
+                f,
+                "\"{}\" is not a list of cards, expected pairs like \"Ah\" or \"Td\"",
+                cards_str
+            ),
+            Self::NotAStartingHand(hand_str) => write!(
+                f,
+                "\"{}\" is not a starting hand, expected e.g. \"AKs\", \"QJo\" or \"TT\"",
+                hand_str
+            ),
+        }
+    }
+}
 
-#[derive(Eq, PartialEq, Hash, Clone)]
+impl Error for CardParseError {}
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum Suit {
     Diamonds,
     Spades,
@@ -20,7 +66,32 @@ impl Suit {
             _ => Self::Clubs,
         }
     }
-    pub fn to_string(&self) -> String {
+    pub fn match_char_with_suit(character: char) -> Option<Suit> {
+        match character.to_ascii_lowercase() {
+            'd' => Some(Self::Diamonds),
+            's' => Some(Self::Spades),
+            'h' => Some(Self::Hearts),
+            'c' => Some(Self::Clubs),
+            _ => None,
+        }
+    }
+    pub fn get_value(&self) -> u32 {
+        match self {
+            Self::Diamonds => 0,
+            Self::Spades => 1,
+            Self::Hearts => 2,
+            Self::Clubs => 3,
+        }
+    }
+    pub fn to_char(&self) -> char {
+        match self {
+            Self::Diamonds => 'd',
+            Self::Spades => 's',
+            Self::Hearts => 'h',
+            Self::Clubs => 'c',
+        }
+    }
+    pub fn to_long_string(&self) -> String {
         match self {
             Self::Diamonds => "Diamonds".to_string(),
             Self::Clubs => "Clubs".to_string(),
@@ -30,7 +101,7 @@ impl Suit {
     }
 }
 
-#[derive(Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum CardNumber {
     BottomAce,
     Two,
@@ -69,6 +140,25 @@ impl CardNumber {
         }
     }
 
+    pub fn match_char_with_card_num(character: char) -> Option<CardNumber> {
+        match character.to_ascii_uppercase() {
+            '2' => Some(Self::Two),
+            '3' => Some(Self::Three),
+            '4' => Some(Self::Four),
+            '5' => Some(Self::Five),
+            '6' => Some(Self::Six),
+            '7' => Some(Self::Seven),
+            '8' => Some(Self::Eight),
+            '9' => Some(Self::Nine),
+            'T' => Some(Self::Ten),
+            'J' => Some(Self::Jack),
+            'Q' => Some(Self::Queen),
+            'K' => Some(Self::King),
+            'A' => Some(Self::Ace),
+            _ => None,
+        }
+    }
+
     pub fn get_value(&self) -> u32 {
         match self {
             Self::BottomAce => 1,
@@ -88,7 +178,26 @@ impl CardNumber {
         }
     }
 
-    pub fn to_string(&self) -> String {
+    pub fn to_char(&self) -> char {
+        match self {
+            Self::BottomAce => 'A',
+            Self::Two => '2',
+            Self::Three => '3',
+            Self::Four => '4',
+            Self::Five => '5',
+            Self::Six => '6',
+            Self::Seven => '7',
+            Self::Eight => '8',
+            Self::Nine => '9',
+            Self::Ten => 'T',
+            Self::Jack => 'J',
+            Self::Queen => 'Q',
+            Self::King => 'K',
+            Self::Ace => 'A',
+        }
+    }
+
+    pub fn to_long_string(&self) -> String {
         match self {
             Self::BottomAce => "Ace".to_string(),
             Self::Two => "Two".to_string(),
@@ -127,29 +236,87 @@ impl CardNumber {
     }
 }
 
-#[derive(Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct Card {
     pub suit: Suit,
     pub number: CardNumber,
 }
 
 impl Card {
-    pub fn to_string(&self) -> String {
-        return vec![self.number.to_string(), self.suit.to_string()].join(" of ");
+    pub fn to_long_string(&self) -> String {
+        return [self.number.to_long_string(), self.suit.to_long_string()].join(" of ");
+    }
+
+    /// Index of the card from 0 to 51, ordered by card number and then by suit.
+    pub fn get_index(&self) -> usize {
+        return ((self.number.get_value() - TWO) * NUM_SUITS + self.suit.get_value()) as usize;
+    }
+
+    pub fn from_index(index: usize) -> Card {
+        return Card {
+            suit: Suit::match_int_with_suit(index as u32 % NUM_SUITS),
+            number: CardNumber::match_int_with_card_num(index as u32 / NUM_SUITS + TWO),
+        };
+    }
+
+    /// Index of a two card combination from 0 to 1325, the same for either order of the cards.
+    pub fn get_combo_index(cards: &[Card]) -> usize {
+        let first_index = cards[0].get_index();
+        let second_index = cards[1].get_index();
+        let (high_index, low_index) = match first_index > second_index {
+            true => (first_index, second_index),
+            false => (second_index, first_index),
+        };
+        return high_index * (high_index - 1) / 2 + low_index;
     }
 
-    pub fn cards_to_string(cards: &Vec<Card>) -> Vec<String> {
+    /// The two cards of a combination index, the card with the higher index first.
+    pub fn combo_from_index(combo_index: usize) -> Vec<Card> {
+        let mut high_index = 1;
+        while (high_index + 1) * high_index / 2 <= combo_index {
+            high_index += 1;
+        }
+        let low_index = combo_index - high_index * (high_index - 1) / 2;
+        return vec![Card::from_index(high_index), Card::from_index(low_index)];
+    }
+
+    /// Parses cards written as card number followed by suit, e.g. "AhKd", "Ah Kd" or "Ah,Kd".
+    pub fn parse_cards(cards_str: &str) -> Result<Vec<Card>, CardParseError> {
+        let characters = cards_str
+            .chars()
+            .filter(|character| !character.is_whitespace() && *character != ',')
+            .collect::<Vec<char>>();
+        if characters.len() % CARD_NOTATION_LEN != 0 {
+            return Err(CardParseError::WrongLength(cards_str.to_string()));
+        }
+
+        return characters
+            .chunks(CARD_NOTATION_LEN)
+            .map(|card_chars| card_chars.iter().collect::<String>().parse())
+            .collect::<Result<Vec<Card>, CardParseError>>();
+    }
+
+    /// Writes cards in the notation read by `Card::parse_cards`, e.g. "AhKd".
+    pub fn cards_to_notation(cards: &[Card]) -> String {
         return cards
             .iter()
             .map(|card| card.to_string())
+            .collect::<Vec<String>>()
+            .join("");
+    }
+
+    pub fn cards_to_string(cards: &[Card]) -> Vec<String> {
+        return cards
+            .iter()
+            .map(|card| card.to_long_string())
             .collect::<Vec<String>>();
     }
 
-    pub fn cards_to_single_string(cards: &Vec<Card>) -> String {
+    pub fn cards_to_single_string(cards: &[Card]) -> String {
         return Card::cards_to_string(cards).join(" | ");
     }
 
-    pub fn get_shorten_hand_string(cards: &Vec<Card>) -> String {
+    pub fn get_shorten_hand_string(cards: &[Card]) -> String {
         let card_1 = &cards[0];
         let card_2 = &cards[1];
 
@@ -167,3 +334,305 @@ impl Card {
         );
     }
 }
+
+impl fmt::Display for Suit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl FromStr for Suit {
+    type Err = CardParseError;
+
+    fn from_str(suit_str: &str) -> Result<Self, Self::Err> {
+        let mut characters = suit_str.chars();
+        match (characters.next(), characters.next()) {
+            (Some(character), None) => {
+                Suit::match_char_with_suit(character).ok_or(CardParseError::UnknownSuit(character))
+            }
+            _ => Err(CardParseError::WrongLength(suit_str.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for CardNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl FromStr for CardNumber {
+    type Err = CardParseError;
+
+    fn from_str(card_number_str: &str) -> Result<Self, Self::Err> {
+        let mut characters = card_number_str.chars();
+        match (characters.next(), characters.next()) {
+            (Some(character), None) => CardNumber::match_char_with_card_num(character)
+                .ok_or(CardParseError::UnknownCardNumber(character)),
+            _ => Err(CardParseError::WrongLength(card_number_str.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.number, self.suit)
+    }
+}
+
+impl FromStr for Card {
+    type Err = CardParseError;
+
+    fn from_str(card_str: &str) -> Result<Self, Self::Err> {
+        let characters = card_str.trim().chars().collect::<Vec<char>>();
+        if characters.len() != CARD_NOTATION_LEN {
+            return Err(CardParseError::WrongLength(card_str.to_string()));
+        }
+        let number = CardNumber::match_char_with_card_num(characters[0])
+            .ok_or(CardParseError::UnknownCardNumber(characters[0]))?;
+        let suit = Suit::match_char_with_suit(characters[1])
+            .ok_or(CardParseError::UnknownSuit(characters[1]))?;
+        return Ok(Card { suit, number });
+    }
+}
+
+/// One of the 169 starting hands, written "AKs", "QJo" or "TT".
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct StartingHand {
+    pub high: CardNumber,
+    pub low: CardNumber,
+    pub suited: bool,
+}
+
+impl StartingHand {
+    pub fn new(first: CardNumber, second: CardNumber, suited: bool) -> Self {
+        let (high, low) = match first.get_value() >= second.get_value() {
+            true => (first, second),
+            false => (second, first),
+        };
+        let suited = suited && high != low;
+        Self { high, low, suited }
+    }
+
+    pub fn from_cards(cards: &[Card]) -> Self {
+        return StartingHand::new(
+            cards[0].number.clone(),
+            cards[1].number.clone(),
+            cards[0].suit == cards[1].suit,
+        );
+    }
+
+    pub fn is_pair(&self) -> bool {
+        return self.high == self.low;
+    }
+
+    /// Index from 0 to 168 on the 13 by 13 grid of starting hands, with pairs on the diagonal,
+    /// suited hands below it and off suit hands above it.
+    pub fn get_index(&self) -> usize {
+        let high = (self.high.get_value() - TWO) as usize;
+        let low = (self.low.get_value() - TWO) as usize;
+        let (row, column) = match self.suited {
+            true => (high, low),
+            false => (low, high),
+        };
+        return row * NUM_DIFFERENT_CARD_NUMBERS + column;
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        let row = (index / NUM_DIFFERENT_CARD_NUMBERS) as u32 + TWO;
+        let column = (index % NUM_DIFFERENT_CARD_NUMBERS) as u32 + TWO;
+        return StartingHand::new(
+            CardNumber::match_int_with_card_num(row),
+            CardNumber::match_int_with_card_num(column),
+            row > column,
+        );
+    }
+
+    /// Every two card combination of this starting hand, high card first. Pairs have 6,
+    /// suited hands 4 and off suit hands 12.
+    pub fn combos(&self) -> Vec<Vec<Card>> {
+        let mut combos = Vec::new();
+        for high_suit in 0..NUM_SUITS {
+            for low_suit in 0..NUM_SUITS {
+                let is_combo = match (self.is_pair(), self.suited) {
+                    (true, _) => high_suit < low_suit,
+                    (false, true) => high_suit == low_suit,
+                    (false, false) => high_suit != low_suit,
+                };
+                if is_combo {
+                    combos.push(vec![
+                        Card {
+                            suit: Suit::match_int_with_suit(high_suit),
+                            number: self.high.clone(),
+                        },
+                        Card {
+                            suit: Suit::match_int_with_suit(low_suit),
+                            number: self.low.clone(),
+                        },
+                    ]);
+                }
+            }
+        }
+        return combos;
+    }
+}
+
+impl fmt::Display for StartingHand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_pair() {
+            return write!(f, "{}{}", self.high, self.low);
+        }
+        let suited_char = match self.suited {
+            true => SUITED_CHAR,
+            false => OFF_SUIT_CHAR,
+        };
+        write!(f, "{}{}{}", self.high, self.low, suited_char)
+    }
+}
+
+impl FromStr for StartingHand {
+    type Err = CardParseError;
+
+    fn from_str(hand_str: &str) -> Result<Self, Self::Err> {
+        let invalid_hand = || CardParseError::NotAStartingHand(hand_str.to_string());
+        let characters = hand_str.trim().chars().collect::<Vec<char>>();
+        if characters.len() < 2 || characters.len() > 3 {
+            return Err(invalid_hand());
+        }
+
+        let first = CardNumber::match_char_with_card_num(characters[0])
+            .ok_or(CardParseError::UnknownCardNumber(characters[0]))?;
+        let second = CardNumber::match_char_with_card_num(characters[1])
+            .ok_or(CardParseError::UnknownCardNumber(characters[1]))?;
+        let suited = match (characters.get(2), first == second) {
+            (None, true) => false,
+            (Some(suffix), false) if suffix.to_ascii_lowercase() == SUITED_CHAR => true,
+            (Some(suffix), false) if suffix.to_ascii_lowercase() == OFF_SUIT_CHAR => false,
+            _ => return Err(invalid_hand()),
+        };
+        return Ok(StartingHand::new(first, second, suited));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn cards_round_trip_through_notation() {
+        for index in 0..NUM_SUITS as usize * NUM_DIFFERENT_CARD_NUMBERS {
+            let card = Card::from_index(index);
+            assert_eq!(card.to_string().parse::<Card>().unwrap(), card);
+        }
+        assert_eq!(
+            "Th".parse::<Card>().unwrap(),
+            Card {
+                suit: Suit::Hearts,
+                number: CardNumber::Ten
+            }
+        );
+    }
+
+    #[test]
+    fn parses_multi_card_strings() {
+        for cards_str in ["AhKd2c", "Ah Kd 2c", "Ah,Kd,2c"] {
+            let cards = Card::parse_cards(cards_str).unwrap();
+            assert_eq!(Card::cards_to_notation(&cards), "AhKd2c");
+        }
+        assert_eq!(Card::parse_cards("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn parses_starting_hands() {
+        for hand_str in ["AKs", "QJo", "TT", "72o"] {
+            assert_eq!(
+                hand_str.parse::<StartingHand>().unwrap().to_string(),
+                hand_str
+            );
+        }
+        assert_eq!(
+            "KAs".parse::<StartingHand>().unwrap(),
+            "AKs".parse::<StartingHand>().unwrap()
+        );
+        assert!(!"TT".parse::<StartingHand>().unwrap().suited);
+    }
+
+    #[test]
+    fn rejects_malformed_cards() {
+        assert_eq!(
+            "1h".parse::<Card>(),
+            Err(CardParseError::UnknownCardNumber('1'))
+        );
+        assert_eq!("Ax".parse::<Card>(), Err(CardParseError::UnknownSuit('x')));
+        assert_eq!(
+            "Ahh".parse::<Card>(),
+            Err(CardParseError::WrongLength("Ahh".to_string()))
+        );
+        assert_eq!(
+            Card::parse_cards("AhK"),
+            Err(CardParseError::WrongLength("AhK".to_string()))
+        );
+        assert_eq!(
+            Card::parse_cards("AhKz"),
+            Err(CardParseError::UnknownSuit('z'))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_starting_hands() {
+        for hand_str in ["AAs", "AAo", "AK", "AKx", "A", "AKso"] {
+            assert_eq!(
+                hand_str.parse::<StartingHand>(),
+                Err(CardParseError::NotAStartingHand(hand_str.to_string())),
+                "{}",
+                hand_str
+            );
+        }
+        assert_eq!(
+            "ZKs".parse::<StartingHand>(),
+            Err(CardParseError::UnknownCardNumber('Z'))
+        );
+    }
+
+    #[test]
+    fn card_indices_round_trip() {
+        for index in 0..NUM_SUITS as usize * NUM_DIFFERENT_CARD_NUMBERS {
+            assert_eq!(Card::from_index(index).get_index(), index);
+        }
+    }
+
+    #[test]
+    fn combo_indices_cover_every_combo_once() {
+        let mut seen_combos = HashSet::new();
+        for combo_index in 0..NUM_HOLE_CARD_COMBOS {
+            let combo = Card::combo_from_index(combo_index);
+            assert_ne!(combo[0], combo[1]);
+            assert_eq!(Card::get_combo_index(&combo), combo_index);
+            assert_eq!(
+                Card::get_combo_index(&[combo[1].clone(), combo[0].clone()]),
+                combo_index
+            );
+            assert!(seen_combos.insert(Card::cards_to_notation(&combo)));
+        }
+    }
+
+    #[test]
+    fn starting_hand_indices_cover_every_hand_once() {
+        let mut num_combos = 0;
+        for index in 0..NUM_STARTING_HANDS {
+            let starting_hand = StartingHand::from_index(index);
+            assert_eq!(starting_hand.get_index(), index);
+            for combo in starting_hand.combos() {
+                assert_eq!(StartingHand::from_cards(&combo).get_index(), index);
+                num_combos += 1;
+            }
+        }
+        assert_eq!(num_combos, NUM_HOLE_CARD_COMBOS);
+        assert_eq!("AKs".parse::<StartingHand>().unwrap().to_string(), "AKs");
+        assert_ne!(
+            "AKs".parse::<StartingHand>().unwrap().get_index(),
+            "AKo".parse::<StartingHand>().unwrap().get_index()
+        );
+    }
+}
@@ -1,60 +1,498 @@
-use super::card::{Card, CardNumber, Suit, NUM_CARD_NUMBERS, NUM_SUITS, TWO};
-use rand::{thread_rng, Rng};
-use std::collections::HashSet;
+use super::card::Card;
+use super::deck::{Deck, NUM_CARDS_IN_DECK};
+use super::range::Range;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::error::Error;
+use std::fmt;
+use std::ops;
 
-const NUM_COMMUNITY_CARDS: usize = 5;
-const NUM_CARDS_IN_HAND: usize = 2;
+pub type DealerRng = ChaCha8Rng;
+
+pub const NUM_COMMUNITY_CARDS: usize = 5;
+pub const NUM_CARDS_IN_HAND: usize = 2;
+/// The most players a single deck can deal hole cards to after the board.
+pub const MAX_PLAYERS: u32 = ((NUM_CARDS_IN_DECK - NUM_COMMUNITY_CARDS) / NUM_CARDS_IN_HAND) as u32;
+const MAX_RANGE_DEAL_ATTEMPTS: u32 = 10_000;
+
+/// Ranges that gave no hands without a shared card within `MAX_RANGE_DEAL_ATTEMPTS` deals.
+#[derive(Debug, PartialEq)]
+pub struct RangeDealError;
+
+impl fmt::Display for RangeDealError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "could not deal a hand from every range without dealing a card twice in {} attempts",
+            MAX_RANGE_DEAL_ATTEMPTS
+        )
+    }
+}
+
+impl Error for RangeDealError {}
+
+/// The rounds that turn over community cards before the river, which is the showdown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Street {
+    Flop,
+    Turn,
+}
+
+pub const NUM_STREETS: usize = 2;
+pub const STREETS: [Street; NUM_STREETS] = [Street::Flop, Street::Turn];
+
+impl Street {
+    pub fn get_index(&self) -> usize {
+        match self {
+            Self::Flop => 0,
+            Self::Turn => 1,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Flop => "flop",
+            Self::Turn => "turn",
+        }
+    }
+
+    pub fn get_num_community_cards(&self) -> usize {
+        match self {
+            Self::Flop => 3,
+            Self::Turn => 4,
+        }
+    }
+}
 
 pub struct Dealer {
-    dealt_cards: HashSet<Card>,
+    deck: Deck,
+    board: Vec<Card>,
+    rng: DealerRng,
+    /// Cards dealt so far in a deal made street by street.
+    num_dealt_cards: usize,
 }
 
 impl Dealer {
-    pub fn new() -> Self {
+    pub fn new(rng: DealerRng) -> Self {
+        return Dealer::with_known_cards(&[], &[], rng);
+    }
+
+    /// Creates a dealer that keeps `board` as the first community cards and never deals
+    /// any of the board or `dead_cards`.
+    pub fn with_known_cards(board: &[Card], dead_cards: &[Card], rng: DealerRng) -> Self {
+        let known_cards = [board, dead_cards].concat();
         Self {
-            dealt_cards: HashSet::new(),
+            deck: Deck::without(&known_cards),
+            board: board.to_vec(),
+            rng,
+            num_dealt_cards: 0,
         }
     }
 
-    fn generate_card() -> Card {
-        let mut rng = thread_rng();
-        let card_suit: u32 = rng.gen_range(0..=NUM_SUITS);
-        let card_number: u32 = rng.gen_range(TWO..=NUM_CARD_NUMBERS);
+    /// Returns the RNG for one of the independent streams of a seeded run, the same seed and
+    /// stream always produce the same cards.
+    pub fn seeded_rng(seed: u64, stream: u64) -> DealerRng {
+        let mut rng = DealerRng::seed_from_u64(seed);
+        rng.set_stream(stream);
+        return rng;
+    }
 
-        let new_card = Card {
-            suit: Suit::match_int_with_suit(card_suit),
-            number: CardNumber::match_int_with_card_num(card_number),
-        };
-        return new_card;
+    /// Completes the board and deals `num_random_hands` two card hands from the deck, without
+    /// dealing any of `taken_cards`.
+    fn deal_random_cards(
+        &mut self,
+        num_random_hands: usize,
+        taken_cards: &[Card],
+    ) -> (Vec<Card>, Vec<Vec<Card>>) {
+        let num_missing_community_cards = self.num_missing_community_cards();
+        let dealt_cards = self.deck.deal_excluding(
+            num_missing_community_cards + num_random_hands * NUM_CARDS_IN_HAND,
+            taken_cards,
+            &mut self.rng,
+        );
+
+        let mut community_cards: Vec<Card> = self.board.clone();
+        community_cards.extend_from_slice(&dealt_cards[..num_missing_community_cards]);
+
+        let player_cards = split_into_hands(&dealt_cards[num_missing_community_cards..]);
+        return (community_cards, player_cards);
     }
 
-    fn generate_new_card(&mut self) -> Card {
-        let mut new_card = Dealer::generate_card();
-        while self.dealt_cards.contains(&new_card) {
-            new_card = Dealer::generate_card();
+    /// Deals `num_players` two card hands and turns over no community cards yet, the rest of
+    /// the board is then dealt street by street with `deal_street` and `deal_river`. Returns the
+    /// known board and the hands.
+    pub fn deal_hole_cards(&mut self, num_players: u32) -> (Vec<Card>, Vec<Vec<Card>>) {
+        self.num_dealt_cards = num_players as usize * NUM_CARDS_IN_HAND;
+        let dealt_cards = self.deck.deal(self.num_dealt_cards, &mut self.rng);
+        return (self.board.clone(), split_into_hands(dealt_cards));
+    }
+
+    /// Turns over the community cards missing from `community_cards` on `street`.
+    pub fn deal_street(&mut self, street: Street, community_cards: &mut Vec<Card>) {
+        self.deal_community_cards(street.get_num_community_cards(), community_cards);
+    }
+
+    /// Turns over the rest of the board after the turn.
+    pub fn deal_river(&mut self, community_cards: &mut Vec<Card>) {
+        self.deal_community_cards(NUM_COMMUNITY_CARDS, community_cards);
+    }
+
+    fn deal_community_cards(&mut self, num_cards: usize, community_cards: &mut Vec<Card>) {
+        let num_missing_cards = num_cards.saturating_sub(community_cards.len());
+        let dealt_cards =
+            self.deck
+                .deal_more(self.num_dealt_cards, num_missing_cards, &mut self.rng);
+        community_cards.extend_from_slice(dealt_cards);
+        self.num_dealt_cards += num_missing_cards;
+    }
+
+    fn num_missing_community_cards(&self) -> usize {
+        return NUM_COMMUNITY_CARDS - self.board.len();
+    }
+
+    /// The number of different ways the rest of the board can be dealt from the deck.
+    pub fn count_board_completions(&self) -> u64 {
+        return count_combinations(self.deck.cards().len(), self.num_missing_community_cards());
+    }
+
+    /// Iterates over the full boards numbered `boards`, out of the `count_board_completions`
+    /// possible ones, each one exactly once and starting from the known board cards. The
+    /// iteration starts right at `boards.start`, so splitting the boards into ranges splits the
+    /// work too. No cards are dealt to players.
+    pub fn board_completions(&self, boards: ops::Range<u64>) -> BoardCompletions<'_> {
+        let num_boards = boards
+            .end
+            .min(self.count_board_completions())
+            .saturating_sub(boards.start);
+        let num_missing_cards = self.num_missing_community_cards();
+        let num_deck_cards = self.deck.cards().len();
+
+        // skips whole groups of boards sharing the next card until the first board is reached
+        let mut indices = Vec::with_capacity(num_missing_cards);
+        let mut num_boards_to_skip = boards.start;
+        let mut next_index = 0;
+        if num_boards > 0 {
+            for position in 0..num_missing_cards {
+                let num_cards_after = num_missing_cards - position - 1;
+                loop {
+                    let num_boards_with_card =
+                        count_combinations(num_deck_cards - next_index - 1, num_cards_after);
+                    if num_boards_to_skip < num_boards_with_card {
+                        break;
+                    }
+                    num_boards_to_skip -= num_boards_with_card;
+                    next_index += 1;
+                }
+                indices.push(next_index);
+                next_index += 1;
+            }
         }
-        self.dealt_cards.insert(new_card.clone());
-        return new_card;
+
+        return BoardCompletions {
+            deck_cards: self.deck.cards(),
+            board: &self.board,
+            indices,
+            num_boards_left: num_boards,
+        };
     }
 
     pub fn deal(&mut self, num_players: u32) -> (Vec<Card>, Vec<Vec<Card>>) {
-        let mut community_cards: Vec<Card> = vec![];
-        let mut player_cards: Vec<Vec<Card>> = vec![];
-
-        for _ in 0..NUM_COMMUNITY_CARDS {
-            let new_card_ref = Dealer::generate_new_card(self);
-            community_cards.push(new_card_ref);
-        }
-        for _ in 0..num_players {
-            let mut two_card_hand: Vec<Card> = vec![];
-            for _ in 0..NUM_CARDS_IN_HAND {
-                let new_card_ref = Dealer::generate_new_card(self);
-                two_card_hand.push(new_card_ref);
+        return self.deal_random_cards(num_players as usize, &[]);
+    }
+
+    /// Deals one hand from each range, every range hand is dealt again until none of them
+    /// share a card, so the hands follow the joint distribution of the ranges.
+    fn try_deal_range_hands(&mut self, ranges: &[Range]) -> Option<Vec<Vec<Card>>> {
+        for _ in 0..MAX_RANGE_DEAL_ATTEMPTS {
+            let mut range_hands: Vec<Vec<Card>> = Vec::with_capacity(ranges.len());
+            for range in ranges {
+                let combo = range.sample(&mut self.rng);
+                let is_available = combo.iter().all(|card| {
+                    self.deck.cards().contains(card)
+                        && range_hands.iter().all(|hand| !hand.contains(card))
+                });
+                if !is_available {
+                    break;
+                }
+                range_hands.push(combo.to_vec());
             }
+            if range_hands.len() == ranges.len() {
+                return Some(range_hands);
+            }
+        }
+        return None;
+    }
+
+    /// Whether a hand from every range can be dealt, ranges that share too many cards can't be.
+    pub fn can_deal_from_ranges(&mut self, ranges: &[Range]) -> bool {
+        return self.try_deal_range_hands(ranges).is_some();
+    }
+
+    /// Deals `fixed_hands` to the first seats and a hand from each of `ranges` to the seats after
+    /// them, the remaining seats up to `num_players` are random. Ranges that almost always share a
+    /// card can still fail to be dealt after `can_deal_from_ranges` passed.
+    pub fn deal_with_hands(
+        &mut self,
+        num_players: u32,
+        fixed_hands: &[Vec<Card>],
+        ranges: &[Range],
+    ) -> Result<(Vec<Card>, Vec<Vec<Card>>), RangeDealError> {
+        for fixed_hand in fixed_hands {
+            self.deck.remove(fixed_hand);
+        }
+        let range_hands = self.try_deal_range_hands(ranges).ok_or(RangeDealError)?;
+
+        let (community_cards, random_player_cards) = self.deal_random_cards(
+            num_players as usize - fixed_hands.len() - ranges.len(),
+            &range_hands.concat(),
+        );
+
+        let mut player_cards: Vec<Vec<Card>> = fixed_hands
+            .iter()
+            .chain(&range_hands)
+            .map(|known_hand| {
+                let mut two_card_hand = known_hand.clone();
+                two_card_hand.sort_by_key(|card| std::cmp::Reverse(card.number.get_value()));
+                two_card_hand
+            })
+            .collect();
+        player_cards.extend(random_player_cards);
+
+        return Ok((community_cards, player_cards));
+    }
+}
+
+/// The number of ways to choose `num_chosen` of `num_items`.
+fn count_combinations(num_items: usize, num_chosen: usize) -> u64 {
+    if num_chosen > num_items {
+        return 0;
+    }
+    return (0..num_chosen as u64).fold(1, |num_combinations, index| {
+        num_combinations * (num_items as u64 - index) / (index + 1)
+    });
+}
+
+/// Splits dealt cards into two card hands, each sorted from the highest card.
+fn split_into_hands(cards: &[Card]) -> Vec<Vec<Card>> {
+    return cards
+        .chunks(NUM_CARDS_IN_HAND)
+        .map(|two_card_hand| {
+            let mut two_card_hand = two_card_hand.to_vec();
             two_card_hand.sort_by_key(|card| std::cmp::Reverse(card.number.get_value()));
-            player_cards.push(two_card_hand);
+            two_card_hand
+        })
+        .collect::<Vec<Vec<Card>>>();
+}
+
+/// The boards of `Dealer::board_completions`, the missing cards are chosen as combinations of
+/// deck indices in lexicographic order.
+pub struct BoardCompletions<'a> {
+    deck_cards: &'a [Card],
+    board: &'a [Card],
+    indices: Vec<usize>,
+    num_boards_left: u64,
+}
+
+impl BoardCompletions<'_> {
+    /// Moves `indices` to the next combination, returns false after the last one.
+    fn advance(&mut self) -> bool {
+        let num_indices = self.indices.len();
+        let num_deck_cards = self.deck_cards.len();
+        for position in (0..num_indices).rev() {
+            if self.indices[position] < num_deck_cards - num_indices + position {
+                self.indices[position] += 1;
+                for next_position in position + 1..num_indices {
+                    self.indices[next_position] = self.indices[next_position - 1] + 1;
+                }
+                return true;
+            }
+        }
+        return false;
+    }
+}
+
+impl Iterator for BoardCompletions<'_> {
+    type Item = Vec<Card>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.num_boards_left == 0 {
+            return None;
         }
 
-        return (community_cards, player_cards);
+        let mut community_cards = self.board.to_vec();
+        community_cards.extend(
+            self.indices
+                .iter()
+                .map(|&index| self.deck_cards[index].clone()),
+        );
+        self.num_boards_left -= 1;
+        if !self.advance() {
+            self.num_boards_left = 0;
+        }
+        return Some(community_cards);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn assert_completes_every_board(board: &str, dead_cards: &str, expected_num_boards: u64) {
+        let board = Card::parse_cards(board).unwrap();
+        let dead_cards = Card::parse_cards(dead_cards).unwrap();
+        let dealer = Dealer::with_known_cards(&board, &dead_cards, Dealer::seeded_rng(0, 0));
+        assert_eq!(dealer.count_board_completions(), expected_num_boards);
+
+        let mut seen_boards = HashSet::new();
+        for community_cards in dealer.board_completions(0..expected_num_boards) {
+            assert_eq!(community_cards.len(), NUM_COMMUNITY_CARDS);
+            assert_eq!(community_cards[..board.len()], board[..]);
+            assert!(community_cards
+                .iter()
+                .all(|card| !dead_cards.contains(card)));
+
+            let unique_cards: HashSet<&Card> = community_cards.iter().collect();
+            assert_eq!(unique_cards.len(), NUM_COMMUNITY_CARDS);
+
+            let mut dealt_cards: Vec<String> = community_cards[board.len()..]
+                .iter()
+                .map(|card| card.to_string())
+                .collect();
+            dealt_cards.sort();
+            assert!(seen_boards.insert(dealt_cards), "board dealt twice");
+        }
+        assert_eq!(seen_boards.len() as u64, expected_num_boards);
+    }
+
+    #[test]
+    fn completes_every_river() {
+        assert_completes_every_board("Qs7d2cJh", "AsKsQhQd", 44);
+    }
+
+    #[test]
+    fn completes_every_turn_and_river() {
+        assert_completes_every_board("Qs7d2c", "AsKsQhQd", 990);
+    }
+
+    #[test]
+    fn full_board_has_one_completion() {
+        assert_completes_every_board("Qs7d2cJh3h", "AsKsQhQd", 1);
+    }
+
+    #[test]
+    fn board_ranges_split_the_boards_in_order() {
+        let board = Card::parse_cards("Qs7d").unwrap();
+        let dealer = Dealer::with_known_cards(&board, &[], Dealer::seeded_rng(0, 0));
+        let num_boards = dealer.count_board_completions();
+        let every_board = dealer.board_completions(0..num_boards).collect::<Vec<_>>();
+        let split_boards = [
+            0..1,
+            1..7_000,
+            7_000..num_boards - 1,
+            num_boards - 1..num_boards,
+        ]
+        .into_iter()
+        .flat_map(|boards| dealer.board_completions(boards))
+        .collect::<Vec<_>>();
+        assert_eq!(split_boards, every_board);
+        assert_eq!(
+            dealer.board_completions(num_boards..num_boards + 5).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn deals_range_hands_from_their_ranges() {
+        let board = Card::parse_cards("Qs7d2c").unwrap();
+        let hero = Card::parse_cards("AsKs").unwrap();
+        let ranges: Vec<Range> = ["QQ+", "AA, 76s"]
+            .iter()
+            .map(|range_str| range_str.parse().unwrap())
+            .collect();
+        let mut dealer = Dealer::with_known_cards(&board, &[], Dealer::seeded_rng(0, 0));
+
+        for _ in 0..1000 {
+            let (community_cards, player_cards) = dealer
+                .deal_with_hands(4, std::slice::from_ref(&hero), &ranges)
+                .unwrap();
+            assert_eq!(player_cards[0], hero);
+            for (hand, range) in player_cards[1..].iter().zip(&ranges) {
+                assert!(range
+                    .combos()
+                    .iter()
+                    .any(|combo| combo.cards.iter().all(|card| hand.contains(card))));
+            }
+
+            let dealt_cards = [community_cards, player_cards.concat()].concat();
+            let unique_cards: HashSet<&Card> = dealt_cards.iter().collect();
+            assert_eq!(unique_cards.len(), dealt_cards.len());
+        }
+    }
+
+    #[test]
+    fn deals_overlapping_ranges_jointly() {
+        let ranges: Vec<Range> = ["AA", "AA, KK"]
+            .iter()
+            .map(|range_str| range_str.parse().unwrap())
+            .collect();
+        let mut dealer = Dealer::new(Dealer::seeded_rng(1, 0));
+        let num_deals = 60_000;
+        let mut num_second_seat_aces = 0;
+        for _ in 0..num_deals {
+            let (_, player_cards) = dealer.deal_with_hands(2, &[], &ranges).unwrap();
+            assert!(player_cards[0]
+                .iter()
+                .all(|card| !player_cards[1].contains(card)));
+            if player_cards[1][0].number == player_cards[0][0].number {
+                num_second_seat_aces += 1;
+            }
+        }
+
+        // 6 x 6 pairs of combos give the second seat kings, only 6 give both seats aces
+        let expected_aces = num_deals as f64 * 6.0 / (6.0 + 36.0);
+        assert!((num_second_seat_aces as f64 - expected_aces).abs() < 0.01 * num_deals as f64);
+        let three_aces = [ranges[0].clone(), ranges[0].clone(), ranges[0].clone()];
+        assert!(!dealer.can_deal_from_ranges(&three_aces));
+        assert_eq!(
+            dealer.deal_with_hands(3, &[], &three_aces),
+            Err(RangeDealError)
+        );
+    }
+
+    #[test]
+    fn deals_the_board_street_by_street() {
+        let board = Card::parse_cards("Qs7d2c").unwrap();
+        let mut dealer = Dealer::with_known_cards(&board, &[], Dealer::seeded_rng(0, 0));
+        for _ in 0..1000 {
+            let (mut community_cards, player_cards) = dealer.deal_hole_cards(6);
+            assert_eq!(community_cards, board);
+            dealer.deal_street(Street::Flop, &mut community_cards);
+            assert_eq!(community_cards, board);
+            dealer.deal_street(Street::Turn, &mut community_cards);
+            assert_eq!(
+                community_cards.len(),
+                Street::Turn.get_num_community_cards()
+            );
+            let turn_cards = community_cards.clone();
+            dealer.deal_river(&mut community_cards);
+            assert_eq!(community_cards[..turn_cards.len()], turn_cards[..]);
+
+            let mut dealt_cards = player_cards.concat();
+            dealt_cards.extend(community_cards);
+            let unique_cards: HashSet<&Card> = dealt_cards.iter().collect();
+            assert_eq!(
+                unique_cards.len(),
+                6 * NUM_CARDS_IN_HAND + NUM_COMMUNITY_CARDS
+            );
+        }
+    }
+
+    #[test]
+    fn counts_every_preflop_board() {
+        let dead_cards = Card::parse_cards("AsKsQhQd").unwrap();
+        let dealer = Dealer::with_known_cards(&[], &dead_cards, Dealer::seeded_rng(0, 0));
+        assert_eq!(dealer.count_board_completions(), 1_712_304);
+        assert_eq!(dealer.board_completions(0..u64::MAX).count(), 1_712_304);
     }
 }
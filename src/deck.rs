@@ -0,0 +1,213 @@
+use super::card::{Card, CardNumber, Suit, NUM_CARD_NUMBERS, NUM_SUITS, TWO};
+use rand::Rng;
+use std::ops;
+
+pub const NUM_CARDS_IN_DECK: usize = 52;
+
+pub struct Deck {
+    cards: Vec<Card>,
+}
+
+impl Deck {
+    pub fn new() -> Self {
+        let mut cards = Vec::with_capacity(NUM_CARDS_IN_DECK);
+        for suit in 0..NUM_SUITS {
+            for number in TWO..=NUM_CARD_NUMBERS {
+                cards.push(Card {
+                    suit: Suit::match_int_with_suit(suit),
+                    number: CardNumber::match_int_with_card_num(number),
+                });
+            }
+        }
+        Self { cards }
+    }
+
+    /// Creates a deck holding every card except `removed_cards`.
+    pub fn without(removed_cards: &[Card]) -> Self {
+        let mut deck = Deck::new();
+        deck.remove(removed_cards);
+        return deck;
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        return &self.cards;
+    }
+
+    pub fn remove(&mut self, removed_cards: &[Card]) {
+        self.cards.retain(|card| !removed_cards.contains(card));
+    }
+
+    /// Deals `num_cards` cards with a partial Fisher–Yates shuffle. Every call is a fresh,
+    /// uniformly random draw from the whole deck, so the deck never needs to be reset.
+    pub fn deal<R: Rng>(&mut self, num_cards: usize, rng: &mut R) -> &[Card] {
+        return self.deal_excluding(num_cards, &[], rng);
+    }
+
+    /// Deals like `deal` but never any of `excluded_cards`, which stay in the deck for later
+    /// deals. The excluded cards are swapped behind the cards that can be dealt.
+    pub fn deal_excluding<R: Rng>(
+        &mut self,
+        num_cards: usize,
+        excluded_cards: &[Card],
+        rng: &mut R,
+    ) -> &[Card] {
+        let mut num_available = self.cards.len();
+        for excluded_card in excluded_cards {
+            if let Some(index) = self.cards[..num_available]
+                .iter()
+                .position(|card| card == excluded_card)
+            {
+                num_available -= 1;
+                self.cards.swap(index, num_available);
+            }
+        }
+
+        assert!(
+            num_cards <= num_available,
+            "Cannot deal {} cards from a deck of {}",
+            num_cards,
+            num_available
+        );
+        self.shuffle(0..num_cards, num_available, rng);
+        return &self.cards[..num_cards];
+    }
+
+    /// Continues the last `deal`, which dealt `num_dealt_cards`, with `num_cards` more cards that
+    /// are never any of the cards already dealt.
+    pub fn deal_more<R: Rng>(
+        &mut self,
+        num_dealt_cards: usize,
+        num_cards: usize,
+        rng: &mut R,
+    ) -> &[Card] {
+        let num_available = self.cards.len();
+        let dealt_cards = num_dealt_cards..num_dealt_cards + num_cards;
+        assert!(
+            dealt_cards.end <= num_available,
+            "Cannot deal {} more cards from a deck of {}",
+            num_cards,
+            num_available - num_dealt_cards
+        );
+        self.shuffle(dealt_cards.clone(), num_available, rng);
+        return &self.cards[dealt_cards];
+    }
+
+    /// Swaps a random card of the first `num_available` into each position of `positions`.
+    fn shuffle<R: Rng>(&mut self, positions: ops::Range<usize>, num_available: usize, rng: &mut R) {
+        for index in positions {
+            let swap_index = rng.gen_range(index..num_available);
+            self.cards.swap(index, swap_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::collections::{HashMap, HashSet};
+
+    const NUM_DEALS: usize = 1_000_000;
+    const NUM_CARDS_PER_DEAL: usize = 7;
+    // chi-square critical values at p = 0.001
+    const CRITICAL_VALUE_51_DF: f64 = 87.97;
+    const CRITICAL_VALUE_3_DF: f64 = 16.27;
+
+    fn chi_square<K>(counts: &HashMap<K, u64>, expected: f64) -> f64 {
+        return counts
+            .values()
+            .map(|count| (*count as f64 - expected).powi(2) / expected)
+            .sum();
+    }
+
+    #[test]
+    fn new_deck_has_every_card_once() {
+        let deck = Deck::new();
+        let unique_cards = deck.cards.iter().collect::<HashSet<&Card>>();
+        assert_eq!(deck.cards.len(), NUM_CARDS_IN_DECK);
+        assert_eq!(unique_cards.len(), NUM_CARDS_IN_DECK);
+    }
+
+    #[test]
+    fn without_removes_cards() {
+        let removed = Card::parse_cards("AhKd2c").unwrap();
+        let mut deck = Deck::without(&removed);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        assert_eq!(deck.cards.len(), NUM_CARDS_IN_DECK - removed.len());
+        for _ in 0..1000 {
+            let num_cards = deck.cards.len();
+            let dealt = deck.deal(num_cards, &mut rng);
+            assert!(dealt.iter().all(|card| !removed.contains(card)));
+        }
+    }
+
+    #[test]
+    fn deal_excluding_keeps_excluded_cards() {
+        let excluded = Card::parse_cards("AsAd7c").unwrap();
+        let mut deck = Deck::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        for _ in 0..1000 {
+            let num_cards = NUM_CARDS_IN_DECK - excluded.len();
+            let dealt = deck.deal_excluding(num_cards, &excluded, &mut rng);
+            assert!(dealt.iter().all(|card| !excluded.contains(card)));
+        }
+        assert_eq!(deck.cards.len(), NUM_CARDS_IN_DECK);
+    }
+
+    #[test]
+    fn deals_are_distinct_cards() {
+        let mut deck = Deck::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let dealt = deck.deal(NUM_CARDS_IN_DECK, &mut rng);
+            let unique_cards = dealt.iter().collect::<HashSet<&Card>>();
+            assert_eq!(unique_cards.len(), NUM_CARDS_IN_DECK);
+        }
+    }
+
+    #[test]
+    fn dealing_more_never_repeats_a_card() {
+        let mut deck = Deck::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        for _ in 0..1000 {
+            let mut dealt = deck.deal(4, &mut rng).to_vec();
+            dealt.extend_from_slice(deck.deal_more(4, 3, &mut rng));
+            dealt.extend_from_slice(deck.deal_more(7, 1, &mut rng));
+            let unique_cards = dealt.iter().collect::<HashSet<&Card>>();
+            assert_eq!(unique_cards.len(), 8);
+        }
+    }
+
+    #[test]
+    fn dealing_is_uniform() {
+        let mut deck = Deck::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let mut first_card_counts: HashMap<Card, u64> = HashMap::new();
+        let mut card_counts: HashMap<Card, u64> = HashMap::new();
+        let mut suit_counts: HashMap<Suit, u64> = HashMap::new();
+
+        for _ in 0..NUM_DEALS {
+            let dealt = deck.deal(NUM_CARDS_PER_DEAL, &mut rng);
+            *first_card_counts.entry(dealt[0].clone()).or_default() += 1;
+            for card in dealt {
+                *card_counts.entry(card.clone()).or_default() += 1;
+                *suit_counts.entry(card.suit.clone()).or_default() += 1;
+            }
+        }
+
+        let num_cards_dealt = (NUM_DEALS * NUM_CARDS_PER_DEAL) as f64;
+        assert_eq!(card_counts.len(), NUM_CARDS_IN_DECK);
+        assert!(
+            chi_square(
+                &first_card_counts,
+                NUM_DEALS as f64 / NUM_CARDS_IN_DECK as f64
+            ) < CRITICAL_VALUE_51_DF
+        );
+        assert!(
+            chi_square(&card_counts, num_cards_dealt / NUM_CARDS_IN_DECK as f64)
+                < CRITICAL_VALUE_51_DF
+        );
+        assert!(chi_square(&suit_counts, num_cards_dealt / NUM_SUITS as f64) < CRITICAL_VALUE_3_DF);
+    }
+}
@@ -0,0 +1,190 @@
+use super::card::Card;
+use super::dealer::{Dealer, DealerRng, RangeDealError, MAX_PLAYERS};
+use super::hand_analyser::{Evaluator, HandAnalyser};
+use super::range::Range;
+use std::collections::HashMap;
+use std::ops;
+
+const PERCENT: f64 = 100.0;
+
+#[derive(Debug, PartialEq)]
+pub struct EquityStats {
+    pub iterations: u64,
+    pub wins: u64,
+    pub ties: u64,
+    pub losses: u64,
+    /// Pots won indexed by the number of winners less one, so equity is worked out only when
+    /// reporting and does not depend on the order threads are combined in.
+    pub split_pots: [u64; MAX_PLAYERS as usize],
+}
+
+impl EquityStats {
+    pub fn new() -> Self {
+        Self {
+            iterations: 0,
+            wins: 0,
+            ties: 0,
+            losses: 0,
+            split_pots: [0; MAX_PLAYERS as usize],
+        }
+    }
+
+    /// Records a showdown won by `num_winners` seats, this seat among them if `is_winner`.
+    pub fn record_showdown(&mut self, is_winner: bool, num_winners: usize) {
+        self.iterations += 1;
+        if !is_winner {
+            self.losses += 1;
+            return;
+        }
+        if num_winners == 1 {
+            self.wins += 1;
+        } else {
+            self.ties += 1;
+        }
+        self.split_pots[num_winners - 1] += 1;
+    }
+
+    pub fn combine(&mut self, other: &EquityStats) {
+        self.iterations += other.iterations;
+        self.wins += other.wins;
+        self.ties += other.ties;
+        self.losses += other.losses;
+        for (num_pots, other_num_pots) in self.split_pots.iter_mut().zip(&other.split_pots) {
+            *num_pots += other_num_pots;
+        }
+    }
+
+    fn as_percentage(&self, value: f64) -> f64 {
+        if self.iterations == 0 {
+            return 0.0;
+        }
+        return value / self.iterations as f64 * PERCENT;
+    }
+
+    /// The average share of the pot won, from 0 to 1.
+    pub fn get_equity(&self) -> f64 {
+        let pot_share = self.split_pots.iter().enumerate().fold(
+            0.0,
+            |pot_share, (num_other_winners, num_pots)| {
+                pot_share + *num_pots as f64 / (num_other_winners + 1) as f64
+            },
+        );
+        return self.as_percentage(pot_share) / PERCENT;
+    }
+
+    pub fn to_report_lines(&self) -> Vec<String> {
+        return vec![
+            format!("Win: {:.2}%", self.as_percentage(self.wins as f64)),
+            format!("Tie: {:.2}%", self.as_percentage(self.ties as f64)),
+            format!("Loss: {:.2}%", self.as_percentage(self.losses as f64)),
+            format!("Equity: {:.2}%", self.get_equity() * PERCENT),
+        ];
+    }
+}
+
+/// The stats of every seat of a lineup, and of the first seat split by the combo it was dealt.
+#[derive(Debug, PartialEq)]
+pub struct LineupStats {
+    pub seats: Vec<EquityStats>,
+    /// Keyed by `Card::get_combo_index`.
+    pub first_seat_combos: HashMap<usize, EquityStats>,
+}
+
+impl LineupStats {
+    pub fn new(num_players: u32) -> Self {
+        Self {
+            seats: (0..num_players).map(|_| EquityStats::new()).collect(),
+            first_seat_combos: HashMap::new(),
+        }
+    }
+
+    fn record_showdown(&mut self, player_cards: &[Vec<Card>], winning_players: &[usize]) {
+        for (seat, seat_stats) in self.seats.iter_mut().enumerate() {
+            seat_stats.record_showdown(winning_players.contains(&seat), winning_players.len());
+        }
+        self.first_seat_combos
+            .entry(Card::get_combo_index(&player_cards[0]))
+            .or_insert_with(EquityStats::new)
+            .record_showdown(winning_players.contains(&0), winning_players.len());
+    }
+
+    pub fn combine(&mut self, other: &LineupStats) {
+        for (seat_stats, other_seat_stats) in self.seats.iter_mut().zip(&other.seats) {
+            seat_stats.combine(other_seat_stats);
+        }
+        for (combo_index, other_combo_stats) in &other.first_seat_combos {
+            self.first_seat_combos
+                .entry(*combo_index)
+                .or_insert_with(EquityStats::new)
+                .combine(other_combo_stats);
+        }
+    }
+}
+
+/// The hands and cards known before dealing. Seats without a fixed hand or range are dealt at
+/// random.
+pub struct Lineup {
+    pub num_players: u32,
+    pub fixed_hands: Vec<Vec<Card>>,
+    /// Ranges of the seats after the fixed hands, blocked combos already removed.
+    pub ranges: Vec<Range>,
+    pub board: Vec<Card>,
+    pub dead_cards: Vec<Card>,
+}
+
+impl Lineup {
+    /// Every card in the fixed hands, on the board or dead.
+    pub fn get_known_cards(&self) -> Vec<Card> {
+        let mut known_cards = self.fixed_hands.concat();
+        known_cards.extend(self.board.iter().cloned());
+        known_cards.extend(self.dead_cards.iter().cloned());
+        return known_cards;
+    }
+
+    pub fn new_dealer(&self, rng: DealerRng) -> Dealer {
+        let out_of_deck_cards = [self.fixed_hands.concat(), self.dead_cards.clone()].concat();
+        return Dealer::with_known_cards(&self.board, &out_of_deck_cards, rng);
+    }
+}
+
+/// Deals one hand for the lineup and records the result for every seat.
+pub fn simulate_lineup(
+    dealer: &mut Dealer,
+    lineup: &Lineup,
+    evaluator: Evaluator,
+    lineup_stats: &mut LineupStats,
+) -> Result<(), RangeDealError> {
+    let (community_cards, player_cards) =
+        dealer.deal_with_hands(lineup.num_players, &lineup.fixed_hands, &lineup.ranges)?;
+    record_showdown(&community_cards, &player_cards, evaluator, lineup_stats);
+    return Ok(());
+}
+
+/// Plays a fully known lineup on the boards numbered `boards` of `dealer`'s board completions.
+pub fn enumerate_lineup(
+    dealer: &Dealer,
+    lineup: &Lineup,
+    evaluator: Evaluator,
+    boards: ops::Range<u64>,
+    lineup_stats: &mut LineupStats,
+) {
+    for community_cards in dealer.board_completions(boards) {
+        record_showdown(
+            &community_cards,
+            &lineup.fixed_hands,
+            evaluator,
+            lineup_stats,
+        );
+    }
+}
+
+fn record_showdown(
+    community_cards: &[Card],
+    player_cards: &[Vec<Card>],
+    evaluator: Evaluator,
+    lineup_stats: &mut LineupStats,
+) {
+    let analyser = HandAnalyser::new(community_cards, player_cards, evaluator);
+    let winning_players = HandAnalyser::find_winning_players(&analyser.get_hand_ranks());
+    lineup_stats.record_showdown(player_cards, &winning_players);
+}
@@ -1,12 +1,254 @@
 use csv::{ReaderBuilder, WriterBuilder};
 
-use ::std::collections::HashMap;
+use std::collections::HashMap;
 use std::error::Error;
-use std::fs::{File, OpenOptions};
-use std::path::Path;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::{NUM_STATS, NUM_TOTAL_STATS};
+use super::hand_analyser::EVALUATOR_VERSION;
+use super::stats::{CountOverflowError, HandStats, HandStatsTable, Stats};
 
+/// Version of the file format, bumped whenever the metadata or the columns change meaning.
+pub const SCHEMA_VERSION: u32 = 2;
+/// Schema version given to legacy files, which predate the metadata block.
+const LEGACY_SCHEMA_VERSION: u32 = 0;
+const METADATA_PREFIX: &str = "#";
+const HAND_COLUMN: &str = "hand";
+/// Legacy files start with the number of iterations and of unique hands, followed by the seeds.
+const NUM_LEGACY_TOTALS: usize = 2;
+const SECONDS_PER_DAY: u64 = 86_400;
+/// Directory next to the output files holding a copy of each file from before its last write.
+const BACKUP_DIRECTORY: &str = "output_backups";
+const LOCK_EXTENSION: &str = "lock";
+const TEMP_EXTENSION: &str = "tmp";
+
+type FileData = (HandStatsTable, FileMetadata);
+
+/// What an output file holds besides the hand rows.
+#[derive(Debug, PartialEq)]
+pub struct FileMetadata {
+    /// Version of the format the file was read in, files are always written in `SCHEMA_VERSION`.
+    pub schema_version: u32,
+    pub num_players: u32,
+    pub verbose: bool,
+    pub num_iterations: u64,
+    /// Seed of every run added to the file, oldest first.
+    pub seeds: Vec<u64>,
+    /// When the first run was written, unknown for files converted from the legacy format.
+    pub created_at: Option<String>,
+}
+
+impl FileMetadata {
+    pub fn new(num_players: u32, verbose: bool) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            num_players,
+            verbose,
+            num_iterations: 0,
+            seeds: vec![],
+            created_at: Some(format_unix_time(get_unix_time())),
+        }
+    }
+
+    /// The metadata lines of the file, `updated_at` being the time of writing.
+    fn to_lines(&self, num_unique_hands: usize) -> Vec<(&'static str, String)> {
+        let seeds = self
+            .seeds
+            .iter()
+            .map(|seed| seed.to_string())
+            .collect::<Vec<String>>();
+        let mut lines = vec![
+            ("schema_version", SCHEMA_VERSION.to_string()),
+            ("crate_version", env!("CARGO_PKG_VERSION").to_string()),
+            ("num_players", self.num_players.to_string()),
+            ("verbose", self.verbose.to_string()),
+            ("evaluator_version", EVALUATOR_VERSION.to_string()),
+            ("iterations", self.num_iterations.to_string()),
+            ("unique_hands", num_unique_hands.to_string()),
+            ("seeds", seeds.join(" ")),
+        ];
+        if let Some(created_at) = &self.created_at {
+            lines.push(("created_at", created_at.clone()));
+        }
+        lines.push(("updated_at", format_unix_time(get_unix_time())));
+        return lines;
+    }
+
+    /// Adds the runs of another file, which must hold the same player count and verbosity in the
+    /// same format version and share no seed. The merged file was created when the older of the
+    /// two was.
+    pub fn combine(&mut self, other: &FileMetadata) -> Result<(), Box<dyn Error>> {
+        if other.num_players != self.num_players || other.verbose != self.verbose {
+            return Err(format!(
+                "Cannot merge {} players{} with {} players{}",
+                self.num_players,
+                if self.verbose { " verbose" } else { "" },
+                other.num_players,
+                if other.verbose { " verbose" } else { "" }
+            )
+            .into());
+        }
+        if (other.schema_version == LEGACY_SCHEMA_VERSION)
+            != (self.schema_version == LEGACY_SCHEMA_VERSION)
+        {
+            return Err(format!(
+                "Cannot merge format version {} with format version {}, version {} is legacy",
+                self.schema_version, other.schema_version, LEGACY_SCHEMA_VERSION
+            )
+            .into());
+        }
+        self.check_seeds_are_new(&other.seeds)?;
+        self.num_iterations = self
+            .num_iterations
+            .checked_add(other.num_iterations)
+            .ok_or_else(|| CountOverflowError("iterations".to_string()))?;
+        self.seeds.extend(&other.seeds);
+        // the times are all written in the same format, so the earliest sorts first
+        self.created_at = match (self.created_at.take(), &other.created_at) {
+            (Some(created_at), Some(other_created_at)) => {
+                Some(created_at.min(other_created_at.clone()))
+            }
+            (created_at, other_created_at) => created_at.or(other_created_at.clone()),
+        };
+        return Ok(());
+    }
+
+    /// Fails if any of `seeds` was already run into the file. The same seed always deals the same
+    /// hands, which would then be counted twice.
+    pub fn check_seeds_are_new(&self, seeds: &[u64]) -> Result<(), Box<dyn Error>> {
+        if let Some(seed) = seeds.iter().find(|seed| self.seeds.contains(seed)) {
+            return Err(format!("Seed {} was already run into the file", seed).into());
+        }
+        return Ok(());
+    }
+
+    /// Adds a run of `num_iterations` dealt from `seed`. A run that dealt nothing leaves its seed
+    /// free to be run later.
+    pub fn add_run(&mut self, seed: u64, num_iterations: u64) -> Result<(), Box<dyn Error>> {
+        self.check_seeds_are_new(&[seed])?;
+        self.num_iterations = self
+            .num_iterations
+            .checked_add(num_iterations)
+            .ok_or_else(|| CountOverflowError("iterations".to_string()))?;
+        if num_iterations > 0 {
+            self.seeds.push(seed);
+        }
+        return Ok(());
+    }
+
+    /// Parses the `key: value` lines of a metadata block, which must match the expected player
+    /// count and verbosity and come from a schema and evaluator this build understands.
+    fn from_lines(lines: &[&str], num_players: u32, verbose: bool) -> Result<Self, Box<dyn Error>> {
+        let values = lines
+            .iter()
+            .filter_map(|line| line.trim_start_matches(METADATA_PREFIX).split_once(':'))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect::<HashMap<&str, &str>>();
+
+        let schema_version: u32 = parse_metadata_value(&values, "schema_version")?;
+        if schema_version > SCHEMA_VERSION {
+            return Err(format!(
+                "Schema version {} is newer than this build's version {}",
+                schema_version, SCHEMA_VERSION
+            )
+            .into());
+        }
+        let evaluator_version: u32 = parse_metadata_value(&values, "evaluator_version")?;
+        if evaluator_version != EVALUATOR_VERSION {
+            return Err(format!(
+                "Hands were ranked by evaluator version {}, this build uses version {}",
+                evaluator_version, EVALUATOR_VERSION
+            )
+            .into());
+        }
+        let metadata = Self {
+            schema_version,
+            num_players: parse_metadata_value(&values, "num_players")?,
+            verbose: parse_metadata_value(&values, "verbose")?,
+            num_iterations: parse_metadata_value(&values, "iterations")?,
+            seeds: values
+                .get("seeds")
+                .unwrap_or(&"")
+                .split_whitespace()
+                .map(|seed| seed.parse())
+                .collect::<Result<Vec<u64>, _>>()?,
+            created_at: values.get("created_at").map(|value| value.to_string()),
+        };
+        if metadata.num_players != num_players || metadata.verbose != verbose {
+            return Err(format!(
+                "Expected {} players{}, the file holds {} players{}",
+                num_players,
+                if verbose { " verbose" } else { "" },
+                metadata.num_players,
+                if metadata.verbose { " verbose" } else { "" }
+            )
+            .into());
+        }
+        return Ok(metadata);
+    }
+}
+
+fn parse_metadata_value<T: FromStr>(
+    values: &HashMap<&str, &str>,
+    key: &str,
+) -> Result<T, Box<dyn Error>> {
+    return values
+        .get(key)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("Missing or invalid metadata {}", key).into());
+}
+
+fn get_unix_time() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+}
+
+/// Formats seconds since the Unix epoch as a UTC time like "2024-05-01T12:30:00Z".
+fn format_unix_time(unix_time: u64) -> String {
+    let seconds_of_day = unix_time % SECONDS_PER_DAY;
+    // days to a civil date, following Howard Hinnant's days_from_civil inverse
+    let days = (unix_time / SECONDS_PER_DAY) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+    return format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    );
+}
+
+/// Reads and writes the stats of every hand. A file starts with a metadata block of
+/// `# key: value` lines: `schema_version`, `crate_version`, `num_players`, `verbose`,
+/// `evaluator_version`, `iterations`, `unique_hands`, `seeds` (space separated, one per run),
+/// `created_at` and `updated_at` (UTC). After it comes a header row naming every column, `hand`
+/// followed by `HandStats::get_column_names`, and one row per hand dealt, sorted by hand.
+///
+/// Legacy files, without metadata and with every row in a single quoted field, are still read
+/// and are written back in the current format.
+///
+/// Writes go to a temporary file that replaces the output in a single rename, so a crash leaves
+/// either the old or the new file. The file it replaces is kept in `output_backups`.
 pub struct FileIO {
     file_path: String,
 }
@@ -15,79 +257,427 @@ impl FileIO {
     pub fn new(file_path: String) -> Self {
         Self { file_path }
     }
-    fn parse_total_data_from_str(str: &str) -> [u32; NUM_TOTAL_STATS] {
+    pub fn get_file_path(&self) -> &str {
+        return &self.file_path;
+    }
+    fn with_extension(&self, extension: &str) -> PathBuf {
+        return PathBuf::from(format!("{}.{}", self.file_path, extension));
+    }
+    fn get_backup_path(&self) -> PathBuf {
+        let path = Path::new(&self.file_path);
+        let directory = path.parent().unwrap_or(Path::new(""));
+        return directory
+            .join(BACKUP_DIRECTORY)
+            .join(path.file_name().unwrap_or_default());
+    }
+    /// Takes an advisory lock on the file, held until the returned lock file is dropped, so two
+    /// runs never add to the same file at once. Fails straight away if another run holds it.
+    pub fn lock(&self) -> Result<File, Box<dyn Error>> {
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.with_extension(LOCK_EXTENSION))?;
+        return match lock_file.try_lock() {
+            Ok(()) => Ok(lock_file),
+            Err(TryLockError::WouldBlock) => {
+                Err(format!("{} is in use by another run", self.file_path).into())
+            }
+            Err(TryLockError::Error(err)) => Err(err.into()),
+        };
+    }
+    /// Parses the totals line, the totals are followed by the seed of every run so far. Files
+    /// written with 32 bit counts read the same.
+    fn parse_total_data_from_str(str: &str) -> (u64, Vec<u64>) {
         let split_data = str
             .split(",")
             .filter_map(|value| value.trim().parse().ok())
-            .collect::<Vec<u32>>();
-        let mut total_stats = [0; NUM_TOTAL_STATS];
-        for i in 0..NUM_TOTAL_STATS {
-            total_stats[i] = split_data[i];
-        }
+            .collect::<Vec<u64>>();
+        let num_iterations = split_data.first().copied().unwrap_or(0);
+        let seeds = split_data.into_iter().skip(NUM_LEGACY_TOTALS).collect();
 
-        return total_stats;
+        return (num_iterations, seeds);
     }
-    fn parse_data_from_str(str: &str) -> Option<(String, [u32; NUM_STATS])> {
+    fn parse_data_from_str(str: &str) -> Option<(String, HandStats)> {
         let mut split_data = str.split(",");
         if let Some(hand) = split_data.next() {
-            let mut counts = [0; NUM_STATS];
-            let parsed_counts = split_data
-                .filter_map(|count_str| count_str.trim().parse().ok())
-                .collect::<Vec<u32>>();
-            for i in 0..NUM_STATS {
-                counts[i] = parsed_counts[i];
-            }
-            return Some((hand.trim().to_string(), counts));
+            let values = split_data.collect::<Vec<&str>>();
+            let hand_stats = HandStats::from_values(&values)?;
+            return Some((hand.trim().to_string(), hand_stats));
         }
         return None;
     }
-    pub fn read_from_file(
-        &self,
-    ) -> Result<(HashMap<String, [u32; NUM_STATS]>, [u32; NUM_TOTAL_STATS]), Box<dyn Error>> {
-        if !Path::new(&self.file_path).exists() {
-            return Ok((HashMap::new(), [0; NUM_TOTAL_STATS]));
-        }
-        let file = File::open(&self.file_path)?;
-        let mut reader = ReaderBuilder::new().has_headers(false).from_reader(file);
-        let mut response_data = HashMap::new();
+    /// Adds a hand's stats onto the table, hands can appear more than once in verbose legacy
+    /// files with the pair's cards in either order.
+    fn add_hand(
+        hand_stats: &mut HandStatsTable,
+        name_indices: &HashMap<String, usize>,
+        hand: String,
+        stats: &HandStats,
+    ) -> Result<(), Box<dyn Error>> {
+        let index = name_indices
+            .get(&hand)
+            .ok_or(format!("Unknown hand {}", hand))?;
+        hand_stats
+            .get_mut(*index)
+            .combine(stats)
+            .ok_or(CountOverflowError(hand))?;
+        return Ok(());
+    }
+    fn read_legacy(
+        contents: &str,
+        num_players: u32,
+        verbose: bool,
+    ) -> Result<FileData, Box<dyn Error>> {
+        let mut response_data = HandStatsTable::new(verbose);
+        let name_indices = response_data.get_name_indices();
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(contents.as_bytes());
 
         let total_stats_record = match reader.records().next() {
             Some(total_stats_result) => total_stats_result?,
             None => return Err("Error parsing".into()),
         };
-        let total_stats_str = &total_stats_record[0];
-        let total_stats = FileIO::parse_total_data_from_str(total_stats_str);
+        let (num_iterations, seeds) = FileIO::parse_total_data_from_str(&total_stats_record[0]);
         for result in reader.records() {
             let record = result?;
             let record_as_str = &record[0];
             if let Some((hand, counts)) = FileIO::parse_data_from_str(record_as_str) {
-                response_data.insert(hand, counts);
+                FileIO::add_hand(&mut response_data, &name_indices, hand, &counts)?;
             } else {
                 return Err("Error parsing".into());
             }
         }
-        return Ok((response_data, total_stats));
+        // legacy files don't record their player count, but every iteration deals a hand to
+        // every player, so a file of another player count can't hold the right number of hands
+        let num_hands = response_data
+            .dealt_hands()
+            .iter()
+            .map(|(_, stats)| u128::from(stats.get(Stats::NumberOfHands)))
+            .sum::<u128>();
+        if num_hands != u128::from(num_iterations) * u128::from(num_players) {
+            return Err(format!(
+                "{} hands were dealt in {} iterations, which does not fit a legacy file of {} players",
+                num_hands, num_iterations, num_players
+            )
+            .into());
+        }
+        let metadata = FileMetadata {
+            schema_version: LEGACY_SCHEMA_VERSION,
+            num_players,
+            verbose,
+            num_iterations,
+            seeds,
+            created_at: None,
+        };
+        return Ok((response_data, metadata));
     }
-    pub fn write_to_file(&self, line: String) -> Result<(), Box<dyn Error>> {
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .append(true)
-            .open(&self.file_path)?;
-        let mut writer = WriterBuilder::new().from_writer(file);
-        writer.write_record(&[line])?;
-        writer.flush()?;
+    fn read_current(
+        contents: &str,
+        num_players: u32,
+        verbose: bool,
+    ) -> Result<FileData, Box<dyn Error>> {
+        let metadata_lines = contents
+            .lines()
+            .take_while(|line| line.starts_with(METADATA_PREFIX))
+            .collect::<Vec<&str>>();
+        let metadata = FileMetadata::from_lines(&metadata_lines, num_players, verbose)?;
+
+        let mut response_data = HandStatsTable::new(verbose);
+        let name_indices = response_data.get_name_indices();
+        let mut reader = ReaderBuilder::new()
+            .comment(Some(METADATA_PREFIX.as_bytes()[0]))
+            .from_reader(contents.as_bytes());
+        // columns are found by name, so their order in the file does not matter. Files written
+        // before pots were counted by their number of winners end without those columns.
+        let headers = reader.headers()?.clone();
+        let num_required_columns = 1 + match metadata.schema_version {
+            1 => HandStats::num_values_before_split_pots(),
+            _ => HandStats::get_column_names().len(),
+        };
+        let column_indices = iter::once(HAND_COLUMN.to_string())
+            .chain(HandStats::get_column_names())
+            .take(num_required_columns)
+            .map(|name| {
+                headers
+                    .iter()
+                    .position(|header| header == name)
+                    .ok_or(format!("Missing column {}", name))
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+        for result in reader.records() {
+            let record = result?;
+            let values = column_indices
+                .iter()
+                .map(|index| record.get(*index).unwrap_or_default())
+                .collect::<Vec<&str>>();
+            let counts = HandStats::from_values(&values[1..]).ok_or("Error parsing")?;
+            FileIO::add_hand(
+                &mut response_data,
+                &name_indices,
+                values[0].to_string(),
+                &counts,
+            )?;
+        }
+        return Ok((response_data, metadata));
+    }
+    /// Reads the stats of a `num_players` file, verbose or not, in the current or the legacy
+    /// format. A missing file reads as no stats yet.
+    pub fn read_from_file(
+        &self,
+        num_players: u32,
+        verbose: bool,
+    ) -> Result<FileData, Box<dyn Error>> {
+        if !Path::new(&self.file_path).exists() {
+            return Ok((
+                HandStatsTable::new(verbose),
+                FileMetadata::new(num_players, verbose),
+            ));
+        }
+        let contents = fs::read_to_string(&self.file_path)?;
+        if contents.starts_with(METADATA_PREFIX) {
+            return FileIO::read_current(&contents, num_players, verbose);
+        }
+        return FileIO::read_legacy(&contents, num_players, verbose);
+    }
+    /// Copies the current file into the backup directory, through a temporary file so that the
+    /// last backup is never left half written either.
+    fn back_up(&self) -> Result<(), Box<dyn Error>> {
+        if !Path::new(&self.file_path).exists() {
+            return Ok(());
+        }
+        let backup_path = self.get_backup_path();
+        if let Some(backup_directory) = backup_path.parent() {
+            fs::create_dir_all(backup_directory)?;
+        }
+        let temp_backup_path =
+            PathBuf::from(format!("{}.{}", backup_path.display(), TEMP_EXTENSION));
+        fs::copy(&self.file_path, &temp_backup_path)?;
+        fs::rename(&temp_backup_path, &backup_path)?;
         return Ok(());
     }
-    pub fn clear_file(&self) -> Result<(), Box<dyn Error>> {
-        File::create(&self.file_path)?;
+    /// Replaces the file with the metadata and every dealt hand of `hand_stats`, keeping the
+    /// file it replaces as the backup.
+    pub fn write_to_file(
+        &self,
+        metadata: &FileMetadata,
+        hand_stats: &HandStatsTable,
+    ) -> Result<(), Box<dyn Error>> {
+        self.back_up()?;
+        let temp_path = self.with_extension(TEMP_EXTENSION);
+        let mut file = File::create(&temp_path)?;
+        for (key, value) in metadata.to_lines(hand_stats.num_dealt_hands()) {
+            writeln!(file, "{} {}: {}", METADATA_PREFIX, key, value)?;
+        }
+        let mut writer = WriterBuilder::new().from_writer(file);
+        writer.write_record(
+            iter::once(HAND_COLUMN.to_string()).chain(HandStats::get_column_names()),
+        )?;
+        for (hand, stats) in hand_stats.dealt_hands() {
+            writer.write_record(iter::once(hand).chain(stats.to_values()))?;
+        }
+        // the new file has to be on disk before it replaces the old one
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&temp_path, &self.file_path)?;
         return Ok(());
     }
-    pub fn array_to_string<T: ToString>(array: &[T]) -> String {
-        return array
-            .iter()
-            .map(|val| val.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Card;
+
+    const NUM_PLAYERS: u32 = 6;
+
+    /// An output file in a fresh directory of its own, where its backups go too.
+    fn temp_file(name: &str) -> FileIO {
+        let directory =
+            std::env::temp_dir().join(format!("poker_calculator_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("output_6_players.csv");
+        return FileIO::new(path.to_string_lossy().to_string());
+    }
+
+    fn remove_temp_file(file: &FileIO) {
+        fs::remove_dir_all(Path::new(file.get_file_path()).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn formats_unix_times_as_utc() {
+        assert_eq!(format_unix_time(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_unix_time(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_unix_time(1_792_324_799), "2026-10-18T11:59:59Z");
+    }
+
+    #[test]
+    fn written_files_read_back() {
+        let file = temp_file("round_trip");
+        let mut hand_stats = HandStatsTable::new(false);
+        let aces = hand_stats.get_hand_index(&Card::parse_cards("AhAd").unwrap());
+        let mut aces_stats = HandStats::new();
+        aces_stats.counts = [10, 7, 1];
+        aces_stats.split_pots[..2].copy_from_slice(&[7, 1]);
+        hand_stats.get_mut(aces).combine(&aces_stats).unwrap();
+        let mut metadata = FileMetadata::new(NUM_PLAYERS, false);
+        metadata.num_iterations = 1_000;
+        metadata.seeds = vec![3, 4];
+        file.write_to_file(&metadata, &hand_stats).unwrap();
+
+        let contents = fs::read_to_string(file.get_file_path()).unwrap();
+        assert!(contents.starts_with("# schema_version: 2\n"));
+        assert!(contents.contains("\nhand,hands,wins,ties,pot_share,showdown_high_card,"));
+        assert!(contents.contains("\nA | A Off Suit,10,7,1,7.5,0,"));
+
+        let (read_stats, read_metadata) = file.read_from_file(NUM_PLAYERS, false).unwrap();
+        assert_eq!(read_metadata, metadata);
+        assert_eq!(read_stats.num_dealt_hands(), 1);
+        assert_eq!(read_stats.dealt_hands()[0].1.counts, [10, 7, 1]);
+        assert!(file.read_from_file(NUM_PLAYERS + 1, false).is_err());
+        assert!(file.read_from_file(NUM_PLAYERS, true).is_err());
+        remove_temp_file(&file);
+    }
+
+    #[test]
+    fn reads_legacy_files() {
+        let file = temp_file("legacy");
+        fs::write(
+            file.get_file_path(),
+            "\"10,2,11,12\"\n\"A | A Off Suit,20,9\"\n\"7 | 2 Off Suit,40,3,1,3.5\"\n",
+        )
+        .unwrap();
+        let (hand_stats, metadata) = file.read_from_file(NUM_PLAYERS, false).unwrap();
+        assert_eq!(metadata.num_iterations, 10);
+        assert_eq!(metadata.seeds, vec![11, 12]);
+        assert_eq!(metadata.created_at, None);
+        assert_eq!(hand_stats.num_dealt_hands(), 2);
+        // a legacy file of another player count dealt the wrong number of hands for its runs
+        assert!(file.read_from_file(NUM_PLAYERS + 2, false).is_err());
+        remove_temp_file(&file);
+    }
+
+    #[test]
+    fn reads_files_written_before_split_pots_were_counted() {
+        let file = temp_file("schema_1");
+        let mut metadata = FileMetadata::new(NUM_PLAYERS, false);
+        metadata.num_iterations = 1_000;
+        file.write_to_file(&metadata, &HandStatsTable::new(false))
+            .unwrap();
+        let num_columns = 1 + HandStats::num_values_before_split_pots();
+        let header = iter::once(HAND_COLUMN.to_string())
+            .chain(HandStats::get_column_names())
+            .take(num_columns)
             .collect::<Vec<String>>()
             .join(",");
+        let row = iter::once("A | A Off Suit,10,7,1,7.5")
+            .chain(iter::repeat_n("0", num_columns - 5))
+            .collect::<Vec<&str>>()
+            .join(",");
+        let contents = fs::read_to_string(file.get_file_path()).unwrap();
+        let metadata_lines = contents
+            .lines()
+            .take_while(|line| line.starts_with(METADATA_PREFIX))
+            .collect::<Vec<&str>>()
+            .join("\n")
+            .replace("schema_version: 2", "schema_version: 1");
+        fs::write(
+            file.get_file_path(),
+            format!("{}\n{}\n{}\n", metadata_lines, header, row),
+        )
+        .unwrap();
+
+        let (hand_stats, metadata) = file.read_from_file(NUM_PLAYERS, false).unwrap();
+        assert_eq!(metadata.schema_version, 1);
+        let aces_stats = hand_stats.dealt_hands()[0].1;
+        assert_eq!(aces_stats.legacy_pot_share, 7.5);
+        assert_eq!(aces_stats.get_pot_share(), 7.5);
+        remove_temp_file(&file);
+    }
+
+    #[test]
+    fn merges_only_matching_metadata() {
+        let mut metadata = FileMetadata::new(NUM_PLAYERS, false);
+        metadata.num_iterations = 1_000;
+        metadata.seeds = vec![1];
+        metadata.created_at = Some("2024-05-01T12:30:00Z".to_string());
+        let mut other_metadata = FileMetadata::new(NUM_PLAYERS, false);
+        other_metadata.num_iterations = 2_000;
+        other_metadata.seeds = vec![2, 3];
+        other_metadata.created_at = Some("2023-01-01T00:00:00Z".to_string());
+        metadata.combine(&other_metadata).unwrap();
+        assert_eq!(metadata.num_iterations, 3_000);
+        assert_eq!(metadata.seeds, vec![1, 2, 3]);
+        assert_eq!(metadata.created_at.as_deref(), Some("2023-01-01T00:00:00Z"));
+
+        assert!(metadata
+            .combine(&FileMetadata::new(NUM_PLAYERS + 1, false))
+            .is_err());
+        assert!(metadata
+            .combine(&FileMetadata::new(NUM_PLAYERS, true))
+            .is_err());
+        let mut legacy_metadata = FileMetadata::new(NUM_PLAYERS, false);
+        legacy_metadata.schema_version = LEGACY_SCHEMA_VERSION;
+        assert!(metadata.combine(&legacy_metadata).is_err());
+        let mut overlapping_metadata = FileMetadata::new(NUM_PLAYERS, false);
+        overlapping_metadata.seeds = vec![4, 2];
+        assert!(metadata.combine(&overlapping_metadata).is_err());
+        other_metadata.seeds = vec![5];
+        other_metadata.num_iterations = u64::MAX;
+        assert!(metadata.combine(&other_metadata).is_err());
+        assert_eq!(metadata.num_iterations, 3_000);
+        assert_eq!(metadata.seeds, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn rerunning_a_seed_already_in_the_file_is_refused() {
+        let file = temp_file("rerun");
+        let mut metadata = FileMetadata::new(NUM_PLAYERS, false);
+        metadata.add_run(7, 0).unwrap();
+        assert!(metadata.seeds.is_empty());
+        metadata.add_run(7, 1_000).unwrap();
+        file.write_to_file(&metadata, &HandStatsTable::new(false))
+            .unwrap();
+
+        let (_, mut read_metadata) = file.read_from_file(NUM_PLAYERS, false).unwrap();
+        assert!(read_metadata.check_seeds_are_new(&[8, 7]).is_err());
+        assert!(read_metadata.add_run(7, 1_000).is_err());
+        assert_eq!(read_metadata.num_iterations, 1_000);
+        assert_eq!(read_metadata.seeds, vec![7]);
+        read_metadata.add_run(8, 1_000).unwrap();
+        assert_eq!(read_metadata.seeds, vec![7, 8]);
+        remove_temp_file(&file);
+    }
+
+    #[test]
+    fn writes_replace_the_file_and_keep_a_backup() {
+        let file = temp_file("backup");
+        let hand_stats = HandStatsTable::new(false);
+        let mut metadata = FileMetadata::new(NUM_PLAYERS, false);
+        metadata.num_iterations = 1_000;
+        file.write_to_file(&metadata, &hand_stats).unwrap();
+        assert!(!file.get_backup_path().exists());
+        let first_contents = fs::read_to_string(file.get_file_path()).unwrap();
+
+        metadata.num_iterations = 2_000;
+        file.write_to_file(&metadata, &hand_stats).unwrap();
+        assert_eq!(
+            fs::read_to_string(file.get_backup_path()).unwrap(),
+            first_contents
+        );
+        assert_eq!(file.read_from_file(NUM_PLAYERS, false).unwrap().1, metadata);
+        assert!(!file.with_extension(TEMP_EXTENSION).exists());
+        remove_temp_file(&file);
+    }
+
+    #[test]
+    fn only_one_run_holds_the_lock() {
+        let file = temp_file("lock");
+        let lock = file.lock().unwrap();
+        assert!(file.lock().is_err());
+        drop(lock);
+        assert!(file.lock().is_ok());
+        remove_temp_file(&file);
     }
 }
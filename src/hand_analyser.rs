@@ -1,18 +1,51 @@
+use super::bitmask_evaluator;
 use super::card::{Card, CardNumber, Suit};
 use ::std::collections::{HashMap, HashSet};
 
+use clap::ValueEnum;
+
 const NUM_CARDS_IN_HAND: usize = 5;
 const NUM_CARDS_IN_TRIPLET: usize = 3;
 const NUM_CARDS_IN_QUAD: usize = 4;
 const NUM_CARDS_IN_PAIR: usize = 2;
 
-const STRAIGHT_INDICIES: [usize; 1] = [0]; //Royal flush, straight flush, straight
-const QUAD_INDICIES: [usize; 2] = [0, 4]; // quad
-const FULL_HOUSE_INDICIES: [usize; 2] = [0, 3]; //full house
-const HIGH_CARD_INDICIES: [usize; 5] = [0, 1, 2, 3, 4]; //flush, high card
-const TRIPS_INDICIES: [usize; 3] = [0, 3, 4]; // trips
-const TWO_PAIR_INDICIES: [usize; 3] = [0, 2, 4]; //two pair
-const PAIR_INDICIES: [usize; 4] = [0, 2, 3, 4]; //pair
+const HAND_VALUE_SHIFT: u32 = 20;
+
+pub const NUM_HAND_CATEGORIES: usize = 10;
+/// Bumped whenever hands could rank differently, so stats from older evaluators are not mixed in.
+pub const EVALUATOR_VERSION: u32 = 1;
+/// Names of the hand types from the weakest to the strongest.
+pub const HAND_CATEGORY_NAMES: [&str; NUM_HAND_CATEGORIES] = [
+    "High Card",
+    "Pair",
+    "Two Pair",
+    "Trips",
+    "Straight",
+    "Flush",
+    "Full House",
+    "Quads",
+    "Straight Flush",
+    "Royal Flush",
+];
+const CARD_NUMBER_BITS: u32 = 4;
+
+/// How the best hand out of a player's cards is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Evaluator {
+    /// Groups the cards by suit and by card number and checks each hand type in turn.
+    Grouping,
+    /// Uses bitmasks of card numbers per suit, much faster than grouping.
+    Bitmask,
+}
+
+impl Evaluator {
+    pub fn evaluate(&self, cards: &[Card]) -> HandRank {
+        match self {
+            Self::Grouping => HandAnalyser::evaluate_cards(cards).get_rank(),
+            Self::Bitmask => bitmask_evaluator::evaluate(cards),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub enum Hand {
@@ -60,6 +93,10 @@ impl Hand {
         }
     }
 
+    pub fn get_rank(&self) -> HandRank {
+        return HandRank::from_hand(self);
+    }
+
     pub fn get_cards(&self) -> Vec<Card> {
         match self {
             Self::RoyalFlush(cards) => cards.clone(),
@@ -76,15 +113,60 @@ impl Hand {
     }
 }
 
+/// Strength of an evaluated hand that can be compared with any other hand. Ranks are ordered by
+/// hand type first and then by every card number that breaks ties within that type, so two
+/// hands split the pot exactly when their ranks are equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HandRank(u32);
+
+impl HandRank {
+    /// The cards of every hand type are ordered so that comparing their numbers from first to
+    /// last breaks ties, e.g. a pair is stored as the pair followed by its kickers.
+    pub fn from_hand(hand: &Hand) -> Self {
+        let mut card_numbers = [0; NUM_CARDS_IN_HAND];
+        for (card_number, card) in card_numbers.iter_mut().zip(hand.get_cards()) {
+            *card_number = card.number.get_value();
+        }
+        return HandRank::from_card_numbers(hand.get_value(), &card_numbers);
+    }
+
+    pub fn from_card_numbers(hand_value: u32, card_numbers: &[u32; NUM_CARDS_IN_HAND]) -> Self {
+        let mut rank = hand_value << HAND_VALUE_SHIFT;
+        for (index, card_number) in card_numbers.iter().enumerate() {
+            let shift = CARD_NUMBER_BITS * (NUM_CARDS_IN_HAND - 1 - index) as u32;
+            rank |= card_number << shift;
+        }
+        return HandRank(rank);
+    }
+
+    /// Evaluates the best five card hand out of five to seven cards.
+    pub fn evaluate(cards: &[Card]) -> Self {
+        return bitmask_evaluator::evaluate(cards);
+    }
+
+    /// The hand type, from 1 for a high card up to 10 for a royal flush.
+    pub fn get_hand_value(&self) -> u32 {
+        return self.0 >> HAND_VALUE_SHIFT;
+    }
+
+    /// The index of the hand type in `HAND_CATEGORY_NAMES`.
+    pub fn get_category(&self) -> usize {
+        return (self.get_hand_value() - 1) as usize;
+    }
+
+    pub fn get_hand_name(&self) -> String {
+        return HAND_CATEGORY_NAMES[self.get_category()].to_string();
+    }
+}
+
 pub struct HandAnalyser {
     card_combinations: Vec<Vec<Card>>,
-    player_cards: Vec<Vec<Card>>,
+    evaluator: Evaluator,
 }
 
 impl HandAnalyser {
-    pub fn new(community_cards: Vec<Card>, player_cards: Vec<Vec<Card>>) -> Self {
-        let mut combined_hands =
-            HandAnalyser::get_card_combinations(player_cards.clone(), community_cards);
+    pub fn new(community_cards: &[Card], player_cards: &[Vec<Card>], evaluator: Evaluator) -> Self {
+        let mut combined_hands = HandAnalyser::get_card_combinations(player_cards, community_cards);
 
         for card_set in combined_hands.iter_mut() {
             card_set.sort_by_key(|card| std::cmp::Reverse(card.number.get_value()));
@@ -92,12 +174,12 @@ impl HandAnalyser {
 
         Self {
             card_combinations: combined_hands,
-            player_cards,
+            evaluator,
         }
     }
 
     fn contains_straight_flush(grouping_by_suit: &HashMap<Suit, Vec<Card>>) -> Option<Vec<Card>> {
-        for (_, cards) in grouping_by_suit.into_iter() {
+        for (_, cards) in grouping_by_suit.iter() {
             let straight_flush = HandAnalyser::contains_straight(cards);
             if straight_flush.is_some() {
                 return straight_flush;
@@ -110,7 +192,7 @@ impl HandAnalyser {
         grouping_by_card_number: &HashMap<CardNumber, Vec<Card>>,
         cards: &Vec<Card>,
     ) -> Option<Vec<Card>> {
-        for (card_number, cards_in_card_number) in grouping_by_card_number.into_iter() {
+        for (card_number, cards_in_card_number) in grouping_by_card_number.iter() {
             if cards_in_card_number.len() == NUM_CARDS_IN_QUAD {
                 let mut quad_hand_cards = cards_in_card_number.clone();
                 for card in cards {
@@ -130,41 +212,49 @@ impl HandAnalyser {
     fn contains_full_house(
         grouping_by_card_number: &HashMap<CardNumber, Vec<Card>>,
     ) -> Option<Vec<Card>> {
-        let mut threes_card_number = &CardNumber::Two;
-        let mut full_house_cards: Vec<Card> = vec![];
-        for (card_number, cards_in_card_number) in grouping_by_card_number.into_iter() {
-            if card_number.get_value() > threes_card_number.get_value()
-                && cards_in_card_number.len() >= NUM_CARDS_IN_TRIPLET
+        let mut threes_card_number: Option<&CardNumber> = None;
+        for (card_number, cards_in_card_number) in grouping_by_card_number.iter() {
+            if cards_in_card_number.len() >= NUM_CARDS_IN_TRIPLET
+                && threes_card_number
+                    .is_none_or(|threes| card_number.get_value() > threes.get_value())
             {
-                threes_card_number = card_number;
+                threes_card_number = Some(card_number);
             }
         }
-        if let Some(threes) = grouping_by_card_number.get(threes_card_number) {
-            full_house_cards.extend(threes.clone());
-        }
-        let mut twos_card_number = &CardNumber::Two;
-        for (card_number, cards_in_card_number) in grouping_by_card_number.into_iter() {
-            if card_number.get_value() > twos_card_number.get_value()
-                && cards_in_card_number.len() >= NUM_CARDS_IN_PAIR
+        let threes_card_number = threes_card_number?;
+
+        // a second set of trips can be played as the pair
+        let mut twos_card_number: Option<&CardNumber> = None;
+        for (card_number, cards_in_card_number) in grouping_by_card_number.iter() {
+            if cards_in_card_number.len() >= NUM_CARDS_IN_PAIR
                 && card_number != threes_card_number
+                && twos_card_number.is_none_or(|twos| card_number.get_value() > twos.get_value())
             {
-                twos_card_number = card_number;
+                twos_card_number = Some(card_number);
             }
         }
-        if let Some(twos) = grouping_by_card_number.get(twos_card_number) {
-            full_house_cards.extend(twos.clone());
-        }
-        if full_house_cards.len() == NUM_CARDS_IN_HAND {
-            return Some(full_house_cards);
-        }
+        let twos_card_number = twos_card_number?;
 
-        return None;
+        let mut full_house_cards: Vec<Card> = vec![];
+        full_house_cards.extend(
+            grouping_by_card_number[threes_card_number]
+                .iter()
+                .take(NUM_CARDS_IN_TRIPLET)
+                .cloned(),
+        );
+        full_house_cards.extend(
+            grouping_by_card_number[twos_card_number]
+                .iter()
+                .take(NUM_CARDS_IN_PAIR)
+                .cloned(),
+        );
+        return Some(full_house_cards);
     }
 
     fn contains_flush(grouping_by_suit: &HashMap<Suit, Vec<Card>>) -> Option<Vec<Card>> {
-        for (_, cards) in grouping_by_suit.into_iter() {
-            if cards.len() == NUM_CARDS_IN_HAND {
-                return Some(cards.clone());
+        for (_, cards) in grouping_by_suit.iter() {
+            if cards.len() >= NUM_CARDS_IN_HAND {
+                return Some(cards[..NUM_CARDS_IN_HAND].to_vec());
             }
         }
         return None;
@@ -240,7 +330,7 @@ impl HandAnalyser {
     ) -> Option<Vec<Card>> {
         let mut trips_card_number = &CardNumber::Two;
         let mut trips_cards: Vec<Card> = vec![];
-        for (card_number, cards_in_card_number) in grouping_by_card_number.into_iter() {
+        for (card_number, cards_in_card_number) in grouping_by_card_number.iter() {
             if card_number.get_value() > trips_card_number.get_value()
                 && cards_in_card_number.len() >= NUM_CARDS_IN_TRIPLET
             {
@@ -273,7 +363,7 @@ impl HandAnalyser {
         let mut first_pair_card_number = &CardNumber::Two;
         let mut second_pair_card_number = &CardNumber::Two;
         let mut two_pair_cards: Vec<Card> = vec![];
-        for (card_number, cards_in_card_number) in grouping_by_card_number.into_iter() {
+        for (card_number, cards_in_card_number) in grouping_by_card_number.iter() {
             if card_number.get_value() > first_pair_card_number.get_value()
                 && cards_in_card_number.len() >= NUM_CARDS_IN_PAIR
             {
@@ -289,7 +379,7 @@ impl HandAnalyser {
             return None;
         }
 
-        for (card_number, cards_in_card_number) in grouping_by_card_number.into_iter() {
+        for (card_number, cards_in_card_number) in grouping_by_card_number.iter() {
             if card_number.get_value() > second_pair_card_number.get_value()
                 && cards_in_card_number.len() >= NUM_CARDS_IN_PAIR
                 && card_number != first_pair_card_number
@@ -325,7 +415,7 @@ impl HandAnalyser {
     ) -> Option<Vec<Card>> {
         let mut pair_card_number = &CardNumber::Two;
         let mut pair_cards: Vec<Card> = vec![];
-        for (card_number, cards_in_card_number) in grouping_by_card_number.into_iter() {
+        for (card_number, cards_in_card_number) in grouping_by_card_number.iter() {
             if card_number.get_value() > pair_card_number.get_value()
                 && cards_in_card_number.len() >= NUM_CARDS_IN_PAIR
             {
@@ -352,15 +442,15 @@ impl HandAnalyser {
     }
 
     fn get_card_combinations(
-        player_cards: Vec<Vec<Card>>,
-        community_cards: Vec<Card>,
+        player_cards: &[Vec<Card>],
+        community_cards: &[Card],
     ) -> Vec<Vec<Card>> {
         let card_combiations = player_cards
             .iter()
             .map(|cards| {
                 let mut full_set_cards = vec![];
-                full_set_cards.extend(community_cards.clone());
-                full_set_cards.extend(cards.clone());
+                full_set_cards.extend_from_slice(community_cards);
+                full_set_cards.extend_from_slice(cards);
                 return full_set_cards;
             })
             .collect::<Vec<Vec<Card>>>();
@@ -375,7 +465,7 @@ impl HandAnalyser {
             let card_suit = card.suit.clone();
             cards_by_suit
                 .entry(card_suit)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(card.clone());
         }
 
@@ -389,7 +479,7 @@ impl HandAnalyser {
             let card_number = card.number.clone();
             cards_by_number
                 .entry(card_number)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(card.clone());
         }
 
@@ -458,186 +548,320 @@ impl HandAnalyser {
         return Hand::HighCard(cards[0..NUM_CARDS_IN_HAND].to_vec());
     }
 
-    fn get_winning_hand_in_hand_type_helper(
-        hands: &Vec<(usize, Vec<Card>)>,
-        indicies: &[usize],
-    ) -> Vec<(usize, Vec<Card>)> {
-        let mut best_hands = hands.clone();
-        for index in indicies {
-            if best_hands.len() == 1 {
-                return best_hands;
-            }
-            // check hand at index and get hands/hand_indicies with highest card number at index
-            let mut highest_card_number: u32 = 0;
-            for i in 0..best_hands.len() {
-                let card_number = (&best_hands[i].1[*index].number).get_value();
-                if card_number > highest_card_number {
-                    highest_card_number = card_number;
-                }
-            }
-            best_hands = best_hands
-                .into_iter()
-                .filter(|(_, cards)| cards[*index].number.get_value() == highest_card_number)
-                .collect::<Vec<(usize, Vec<Card>)>>();
-        }
-        return best_hands;
+    /// Returns the best hand out of five to seven cards in any order.
+    pub fn evaluate_cards(cards: &[Card]) -> Hand {
+        let mut sorted_cards = cards.to_vec();
+        sorted_cards.sort_by_key(|card| std::cmp::Reverse(card.number.get_value()));
+        return HandAnalyser::get_best_hand(&sorted_cards);
     }
 
-    fn get_winning_hand_in_hand_type(hands: &Vec<(usize, Hand)>) -> Vec<(usize, Hand)> {
-        let hand_type = &hands[0].1;
-        let mapped_hands = &hands
+    /// Returns the seat of every player who wins (or chops) the pot with these ranks.
+    pub fn find_winning_players(ranks: &[HandRank]) -> Vec<usize> {
+        let Some(strongest_rank) = ranks.iter().max() else {
+            return vec![];
+        };
+        return (0..ranks.len())
+            .filter(|seat| ranks[*seat] == *strongest_rank)
+            .collect::<Vec<usize>>();
+    }
+
+    /// Returns every seat's finishing position with these ranks, 1 for the winners. Tied seats
+    /// share a position and the seats behind them skip the positions they fill, e.g. 1, 2, 2, 4.
+    pub fn find_finishing_positions(ranks: &[HandRank]) -> Vec<usize> {
+        return ranks
             .iter()
-            .map(|(index, hand)| (*index, hand.get_cards()))
-            .collect::<Vec<(usize, Vec<Card>)>>();
-
-        match hand_type {
-            Hand::RoyalFlush(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &STRAIGHT_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::RoyalFlush(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
-            }
-            Hand::StraightFlush(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &STRAIGHT_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::StraightFlush(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
-            }
-            Hand::Quads(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &QUAD_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::Quads(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
-            }
-            Hand::FullHouse(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &FULL_HOUSE_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::FullHouse(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
-            }
-            Hand::Flush(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &HIGH_CARD_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::Flush(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
-            }
-            Hand::Straight(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &STRAIGHT_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::Straight(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
-            }
-            Hand::Trips(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &TRIPS_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::Trips(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
-            }
-            Hand::TwoPair(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &TWO_PAIR_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::TwoPair(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
-            }
-            Hand::Pair(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &PAIR_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::Pair(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
+            .map(|rank| 1 + ranks.iter().filter(|other_rank| *other_rank > rank).count())
+            .collect::<Vec<usize>>();
+    }
+
+    /// Returns the rank of every seat's best hand.
+    pub fn get_hand_ranks(&self) -> Vec<HandRank> {
+        return self
+            .card_combinations
+            .iter()
+            .map(|cards| self.evaluator.evaluate(cards))
+            .collect::<Vec<HandRank>>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deck::Deck;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    const NUM_RANDOM_HANDS: usize = 100_000;
+    const NUM_RANDOM_HANDS_EXHAUSTIVE: usize = 5_000_000;
+    const NUM_ADVERSARIAL_HANDS: usize = 20_000;
+    const NUM_RANDOM_TABLES: usize = 10_000;
+    const EVALUATORS: [Evaluator; 2] = [Evaluator::Grouping, Evaluator::Bitmask];
+
+    /// Scores exactly five cards by counting card numbers, with no shortcuts.
+    fn reference_score_five_cards(cards: &[&Card]) -> HandRank {
+        let mut values = cards
+            .iter()
+            .map(|card| card.number.get_value())
+            .collect::<Vec<u32>>();
+        values.sort_by(|a, b| b.cmp(a));
+
+        let is_flush = cards.iter().all(|card| card.suit == cards[0].suit);
+        let is_wheel = values == [14, 5, 4, 3, 2];
+        let is_straight = is_wheel
+            || values
+                .windows(2)
+                .all(|neighbours| neighbours[0] == neighbours[1] + 1);
+
+        // card numbers ordered by how often they appear, then by value
+        let mut groups: Vec<(usize, u32)> = vec![];
+        for value in &values {
+            match groups
+                .iter_mut()
+                .find(|(_, group_value)| group_value == value)
+            {
+                Some((count, _)) => *count += 1,
+                None => groups.push((1, *value)),
             }
-            Hand::HighCard(_) => {
-                let cards = HandAnalyser::get_winning_hand_in_hand_type_helper(
-                    mapped_hands,
-                    &HIGH_CARD_INDICIES,
-                );
-                return cards
-                    .iter()
-                    .map(|(index, cards)| (*index, Hand::HighCard(cards.clone())))
-                    .collect::<Vec<(usize, Hand)>>();
+        }
+        groups.sort_by(|a, b| b.cmp(a));
+        let counts = groups
+            .iter()
+            .map(|(count, _)| *count)
+            .collect::<Vec<usize>>();
+        let mut grouped_values = [0; NUM_CARDS_IN_HAND];
+        let mut index = 0;
+        for (count, value) in &groups {
+            for _ in 0..*count {
+                grouped_values[index] = *value;
+                index += 1;
             }
+        }
+
+        let straight_values = match is_wheel {
+            true => [5, 4, 3, 2, 14],
+            false => grouped_values,
         };
+
+        let (hand_value, card_numbers) = match (is_straight, is_flush, counts.as_slice()) {
+            (true, true, _) if straight_values[0] == 14 => (10, straight_values),
+            (true, true, _) => (9, straight_values),
+            (_, _, [4, 1]) => (8, grouped_values),
+            (_, _, [3, 2]) => (7, grouped_values),
+            (_, true, _) => (6, grouped_values),
+            (true, _, _) => (5, straight_values),
+            (_, _, [3, 1, 1]) => (4, grouped_values),
+            (_, _, [2, 2, 1]) => (3, grouped_values),
+            (_, _, [2, 1, 1, 1]) => (2, grouped_values),
+            _ => (1, grouped_values),
+        };
+        return HandRank::from_card_numbers(hand_value, &card_numbers);
     }
 
-    pub fn get_winning_hands(&self, verbose: bool) -> Vec<(String, Hand)> {
-        let hands = self
-            .card_combinations
-            .iter()
-            .enumerate()
-            .map(|(index, cards)| (index, HandAnalyser::get_best_hand(cards)))
-            .collect::<Vec<(usize, Hand)>>();
-
-        let mut strongest_hands_value: u32 = 0;
-        for (_, hand) in &hands {
-            if hand.get_value() > strongest_hands_value {
-                strongest_hands_value = hand.get_value();
+    /// Slow but obvious evaluator, the best score of every five card subset.
+    fn reference_evaluate(cards: &[Card]) -> HandRank {
+        let mut best_rank = None;
+        let num_cards = cards.len();
+        for a in 0..num_cards {
+            for b in a + 1..num_cards {
+                for c in b + 1..num_cards {
+                    for d in c + 1..num_cards {
+                        for e in d + 1..num_cards {
+                            let subset = [&cards[a], &cards[b], &cards[c], &cards[d], &cards[e]];
+                            let rank = reference_score_five_cards(&subset);
+                            best_rank = best_rank.max(Some(rank));
+                        }
+                    }
+                }
+            }
+        }
+        return best_rank.expect("At least five cards are needed");
+    }
+
+    fn assert_matches_reference(cards: &[Card]) {
+        let expected = reference_evaluate(cards);
+        for evaluator in EVALUATORS {
+            let rank = evaluator.evaluate(cards);
+            assert_eq!(
+                rank,
+                expected,
+                "{:?} evaluator ranked {} as {} {:x?}, reference says {} {:x?}",
+                evaluator,
+                Card::cards_to_notation(cards),
+                rank.get_hand_name(),
+                rank,
+                expected.get_hand_name(),
+                expected
+            );
+        }
+    }
+
+    fn check_random_hands(num_hands: usize, seed: u64) {
+        let mut deck = Deck::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        for index in 0..num_hands {
+            let num_cards = NUM_CARDS_IN_HAND + index % 3;
+            assert_matches_reference(deck.deal(num_cards, &mut rng));
+        }
+    }
+
+    fn cards_from(card_numbers: &str, suits: &str) -> Vec<Card> {
+        let mut cards = vec![];
+        for card_number in card_numbers.chars() {
+            for suit in suits.chars() {
+                cards.push(format!("{}{}", card_number, suit).parse::<Card>().unwrap());
             }
         }
+        return cards;
+    }
 
-        let strongest_hands = hands
+    /// Deals seven card hands from a deck with only `card_numbers` and `suits`, which makes
+    /// rare hands like six card flushes or double trips common.
+    fn check_restricted_deck(card_numbers: &str, suits: &str, seed: u64) {
+        let restricted_cards = cards_from(card_numbers, suits);
+        let removed_cards = cards_from("23456789TJQKA", "dshc")
             .into_iter()
-            .filter(|(_, hand)| hand.get_value() == strongest_hands_value)
-            .collect::<Vec<(usize, Hand)>>();
-
-        // for (player_num, hand) in &strongest_hands {
-        //     println!("Player: {}, has a {}", player_num, hand.to_string());
-        // }
-        // println!("Winners:");
-        let winning_hands = HandAnalyser::get_winning_hand_in_hand_type(&strongest_hands);
-        // for (player_num, hand) in &winning_hands {
-        //     println!("Player: {}, has a {}", player_num, hand.to_string());
-        //     println!(
-        //         "Player {} hand: {}",
-        //         player_num,
-        //         Card::cards_to_single_string(&self.player_cards[*player_num])
-        //     )
-        // }
-        let winning_player_hands = winning_hands
+            .filter(|card| !restricted_cards.contains(card))
+            .collect::<Vec<Card>>();
+        let mut deck = Deck::without(&removed_cards);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        for _ in 0..NUM_ADVERSARIAL_HANDS {
+            assert_matches_reference(deck.deal(7, &mut rng));
+        }
+    }
+
+    fn rank_of(cards_str: &str) -> HandRank {
+        let cards = Card::parse_cards(cards_str).unwrap();
+        assert_matches_reference(&cards);
+        return Evaluator::Grouping.evaluate(&cards);
+    }
+
+    #[test]
+    fn random_hands_match_reference() {
+        check_random_hands(NUM_RANDOM_HANDS, 0);
+    }
+
+    #[test]
+    #[ignore = "slow, run with `cargo test --release -- --ignored`"]
+    fn millions_of_random_hands_match_reference() {
+        check_random_hands(NUM_RANDOM_HANDS_EXHAUSTIVE, 1);
+    }
+
+    #[test]
+    fn flush_heavy_hands_match_reference() {
+        check_restricted_deck("23456789TJQKA", "hs", 2);
+        check_restricted_deck("23456789TJQKA", "hsd", 3);
+    }
+
+    #[test]
+    fn straight_heavy_hands_match_reference() {
+        check_restricted_deck("A23456", "dshc", 4);
+        check_restricted_deck("9TJQKA", "dshc", 5);
+        check_restricted_deck("A2345", "hs", 6);
+    }
+
+    #[test]
+    fn paired_hands_match_reference() {
+        check_restricted_deck("27K", "dshc", 7);
+        check_restricted_deck("59QA", "dshc", 8);
+        check_restricted_deck("2345", "dshc", 9);
+    }
+
+    #[test]
+    fn known_hands() {
+        assert_eq!(rank_of("AhKhQhJhTh2c3d").get_hand_name(), "Royal Flush");
+        assert_eq!(rank_of("Ah2h3h4h5h9c9d").get_hand_name(), "Straight Flush");
+        assert_eq!(rank_of("2h3h4h5h6h7h8h").get_hand_name(), "Straight Flush");
+        assert_eq!(rank_of("7c7d7h7sKhKdKc").get_hand_name(), "Quads");
+        assert_eq!(rank_of("2h2d2c9h9dAcKs").get_hand_name(), "Full House");
+        assert_eq!(rank_of("Ah2c3d4s5hKhKd").get_hand_name(), "Straight");
+        assert_eq!(rank_of("2h2d2c9sJdAcKs").get_hand_name(), "Trips");
+        assert_eq!(rank_of("AhAdKcKs2h2dQc").get_hand_name(), "Two Pair");
+        assert_eq!(rank_of("AhAd9c8s2h4d6c").get_hand_name(), "Pair");
+        assert_eq!(rank_of("Ah3d9c8s2hJd6c").get_hand_name(), "High Card");
+    }
+
+    #[test]
+    fn known_comparisons() {
+        // a wheel is the lowest straight
+        assert!(rank_of("Ah2c3d4s5h9cJd") < rank_of("2c3d4s5h6h9cJd"));
+        // the third pair can't play, only the best kicker does
+        assert!(rank_of("AhAdKcKs2h2dQc") > rank_of("AhAdKcKs3h3dJc"));
+        assert_eq!(rank_of("AhAdKcKs2h2dQc"), rank_of("AcAsKhKdQd7c5h"));
+        assert_eq!(rank_of("8h8d8c8s2h3d4c"), rank_of("8h8d8c8s2h3d4s"));
+    }
+
+    // The grouping evaluator used to only find flushes of exactly five suited cards, so
+    // Ah9h7h5h3h2hKc and Ah9h7h5h3h2hKh were ranked as High Card.
+    #[test]
+    fn flushes_of_six_and_seven_suited_cards() {
+        assert_eq!(rank_of("Ah9h7h5h3h2hKc").get_hand_name(), "Flush");
+        assert_eq!(rank_of("Ah9h7h5h3h2hKh").get_hand_name(), "Flush");
+        // the best five of six suited cards play
+        assert!(rank_of("Ah9h7h5h3h2hKc") < rank_of("Ah9h7h5h4h2hKc"));
+    }
+
+    // The grouping evaluator used to take every card of the pair's number, so a second set of
+    // trips gave six cards and KhKdKc9h9d9c2s and QhQdQc2h2d2c9s were ranked as Trips.
+    #[test]
+    fn full_houses_of_two_sets_of_trips() {
+        assert_eq!(rank_of("KhKdKc9h9d9c2s").get_hand_name(), "Full House");
+        assert_eq!(rank_of("QhQdQc2h2d2c9s").get_hand_name(), "Full House");
+        // the higher of two sets of trips is played as the three of a kind
+        assert!(rank_of("KhKdKc9h9d9c2s") > rank_of("QhQdQcAhAd9c2s"));
+        assert!(rank_of("QhQdQc2h2d2c9s") < rank_of("QhQdQc3h3d9c2s"));
+    }
+
+    #[test]
+    fn tied_hands_share_finishing_positions() {
+        let board = "Qs7d2c9h3d";
+        let ranks = ["AhAd", "KhKd", "KsKc", "4h5h", "QhJh", "AsAc"]
             .iter()
-            .map(|(player_num, hand)| {
-                let cards_string = if verbose {
-                    Card::cards_to_single_string(&self.player_cards[*player_num])
-                } else {
-                    Card::get_shorten_hand_string(&self.player_cards[*player_num])
-                };
-                (cards_string, hand.clone())
-            })
-            .collect::<Vec<(String, Hand)>>();
-        return winning_player_hands;
+            .map(|hole_cards| rank_of(&format!("{}{}", hole_cards, board)))
+            .collect::<Vec<HandRank>>();
+        assert_eq!(
+            HandAnalyser::find_finishing_positions(&ranks),
+            vec![1, 3, 3, 6, 5, 1]
+        );
+    }
+
+    #[test]
+    fn winning_hands_match_reference() {
+        let mut deck = Deck::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(10);
+        for index in 0..NUM_RANDOM_TABLES {
+            let num_players = 2 + index % 9;
+            let dealt_cards = deck.deal(5 + num_players * 2, &mut rng);
+            let community_cards = dealt_cards[..5].to_vec();
+            let player_cards = dealt_cards[5..]
+                .chunks(2)
+                .map(|cards| cards.to_vec())
+                .collect::<Vec<Vec<Card>>>();
+
+            let reference_ranks = player_cards
+                .iter()
+                .map(|cards| reference_evaluate(&[cards.as_slice(), &community_cards].concat()))
+                .collect::<Vec<HandRank>>();
+            let best_rank = reference_ranks.iter().max().unwrap();
+            let expected_winners = (0..num_players)
+                .filter(|seat| reference_ranks[*seat] == *best_rank)
+                .collect::<Vec<usize>>();
+
+            for evaluator in EVALUATORS {
+                let analyser = HandAnalyser::new(&community_cards, &player_cards, evaluator);
+                let winners = HandAnalyser::find_winning_players(&analyser.get_hand_ranks());
+                assert_eq!(
+                    winners,
+                    expected_winners,
+                    "{:?} evaluator picked the wrong winners, board {}, players {}",
+                    evaluator,
+                    Card::cards_to_notation(&community_cards),
+                    player_cards
+                        .iter()
+                        .map(|cards| Card::cards_to_notation(cards))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                );
+            }
+        }
     }
 }
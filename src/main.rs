@@ -1,115 +1,635 @@
+#![allow(clippy::needless_return)]
+
 mod args;
+mod benchmark;
+mod bitmask_evaluator;
 mod card;
 mod dealer;
+mod deck;
+mod equity;
 mod file_io;
 mod hand_analyser;
 mod logger;
+mod range;
+mod stats;
 
-use args::Args;
-use card::Card;
-use dealer::Dealer;
-use file_io::FileIO;
-use hand_analyser::HandAnalyser;
+use args::{Args, Command};
+use card::{Card, StartingHand};
+use dealer::{Dealer, RangeDealError, Street, NUM_CARDS_IN_HAND, NUM_COMMUNITY_CARDS, STREETS};
+use deck::NUM_CARDS_IN_DECK;
+use equity::{EquityStats, Lineup, LineupStats};
+use file_io::{FileIO, FileMetadata};
+use hand_analyser::{Evaluator, HandAnalyser, HandRank};
 use logger::Logger;
+use range::Range;
+use stats::{CountOverflowError, HandStatsTable};
 
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::ops;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Instant;
 
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
 
 const THOUSAND: u32 = 1000;
-const _NUM_THREADS: usize = 10;
+const HERO_SEAT: usize = 0;
+const CONVERGENCE_CHECK_BATCHES: u32 = 16;
 
-const NUM_STATS: usize = 2;
-const NUM_TOTAL_STATS: usize = 2;
+/// Spreads batches of `THOUSAND` iterations over `num_threads` workers and returns each worker's
+/// result. Every batch gets its own RNG stream, so the combined results only depend on the seed
+/// and the batches run, not on the number of threads.
+fn run_on_threads<T, F>(num_threads: u32, batches: ops::Range<u32>, work: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(Vec<u64>) -> T + Sync,
+{
+    return thread::scope(|scope| {
+        let work = &work;
+        let handles = (0..num_threads)
+            .map(|thread_index| {
+                let batches = (batches.start + thread_index..batches.end)
+                    .step_by(num_threads as usize)
+                    .map(u64::from)
+                    .collect::<Vec<u64>>();
+                scope.spawn(move || work(batches))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Simulation thread panicked"))
+            .collect::<Vec<T>>()
+    });
+}
 
-pub enum Stats {
-    NumberOfHands,
-    NumberOfWins,
+/// Deals one hand and records it for every seat. When `track_streets` is set the board is
+/// turned over street by street and every seat's hand on each street is ranked as well.
+fn simulate(
+    dealer: &mut Dealer,
+    num_players: u32,
+    evaluator: Evaluator,
+    track_streets: bool,
+    hand_stats: &mut HandStatsTable,
+) {
+    if !track_streets {
+        let (community_cards, player_cards) = dealer.deal(num_players);
+        record_deal(community_cards, player_cards, &[], evaluator, hand_stats);
+        return;
+    }
+
+    let (mut community_cards, player_cards) = dealer.deal_hole_cards(num_players);
+    let street_ranks = STREETS.map(|street| {
+        dealer.deal_street(street, &mut community_cards);
+        HandAnalyser::new(&community_cards, &player_cards, evaluator).get_hand_ranks()
+    });
+    dealer.deal_river(&mut community_cards);
+    record_deal(
+        community_cards,
+        player_cards,
+        &street_ranks,
+        evaluator,
+        hand_stats,
+    );
 }
 
-impl Stats {
-    fn get_index(&self) -> usize {
-        match self {
-            Self::NumberOfHands => 0,
-            Self::NumberOfWins => 1,
+/// Records the showdown and finishing positions of every seat of one deal. `street_ranks`
+/// holds every seat's rank on each of `STREETS` in order, or nothing when streets are not
+/// tracked.
+fn record_deal(
+    community_cards: Vec<Card>,
+    player_cards: Vec<Vec<Card>>,
+    street_ranks: &[Vec<HandRank>],
+    evaluator: Evaluator,
+    hand_stats: &mut HandStatsTable,
+) {
+    let hand_indices = player_cards
+        .iter()
+        .map(|cards| hand_stats.get_hand_index(cards))
+        .collect::<Vec<usize>>();
+    let street_leaders = street_ranks
+        .iter()
+        .map(|ranks| HandAnalyser::find_winning_players(ranks))
+        .collect::<Vec<Vec<usize>>>();
+
+    let ranks = HandAnalyser::new(&community_cards, &player_cards, evaluator).get_hand_ranks();
+    let winning_players = HandAnalyser::find_winning_players(&ranks);
+    let winning_rank = ranks[winning_players[0]];
+    let finishing_positions = HandAnalyser::find_finishing_positions(&ranks);
+
+    for (seat, (hand_index, rank)) in hand_indices.into_iter().zip(ranks).enumerate() {
+        let stats = hand_stats.get_mut(hand_index);
+        stats.record_hand(rank);
+        stats.record_finish(finishing_positions[seat]);
+        if winning_players.contains(&seat) {
+            stats.record_win(winning_players.len(), rank);
+        } else {
+            stats.record_loss(rank, winning_rank);
+        }
+
+        for ((street, ranks), leaders) in STREETS.into_iter().zip(street_ranks).zip(&street_leaders)
+        {
+            stats.record_street(street, ranks[seat], leaders.contains(&seat));
+        }
+        if street_leaders
+            .get(Street::Flop.get_index())
+            .is_some_and(|flop_leaders| flop_leaders.contains(&seat))
+            && winning_players.contains(&seat)
+        {
+            stats.record_flop_lead_won();
         }
     }
 }
 
-pub enum TotalStats {
-    NumberOfIterations,
-    NumberOfUniqueHands,
+/// Runs `batches` of the default mode and returns the stats of every hand dealt.
+fn simulate_batches(args: &Args, seed: u64, batches: ops::Range<u32>) -> HandStatsTable {
+    let thread_hand_stats = run_on_threads(args.threads, batches, |batches| {
+        let mut stats = HandStatsTable::new(args.verbose);
+        for batch in batches {
+            let mut dealer = Dealer::new(Dealer::seeded_rng(seed, batch));
+            for _ in 0..THOUSAND {
+                simulate(
+                    &mut dealer,
+                    args.num_players,
+                    args.evaluator,
+                    args.streets,
+                    &mut stats,
+                );
+            }
+        }
+        stats
+    });
+
+    let mut hand_stats = HandStatsTable::new(args.verbose);
+    for stats in thread_hand_stats {
+        // a single run deals far fewer than 2^64 hands
+        hand_stats
+            .combine(&stats)
+            .expect("Counts of a single run overflowed");
+    }
+    return hand_stats;
 }
 
-impl TotalStats {
-    fn get_index(&self) -> usize {
-        match self {
-            Self::NumberOfIterations => 0,
-            Self::NumberOfUniqueHands => 1,
+/// Keeps adding batches onto `hand_stats` until every hand's standard error is below
+/// `target_stderr` or a cap is hit, and returns the number of batches run.
+fn run_until_converged(
+    args: &Args,
+    seed: u64,
+    target_stderr: f64,
+    hand_stats: &mut HandStatsTable,
+) -> Result<u32, CountOverflowError> {
+    let start_time = Instant::now();
+    let max_batches = args.max_thousand_iterations.unwrap_or(u32::MAX);
+
+    let mut num_batches: u32 = 0;
+    loop {
+        // a fixed number of batches per check keeps the stopping point independent of threads
+        let round_end = num_batches
+            .saturating_add(CONVERGENCE_CHECK_BATCHES)
+            .min(max_batches);
+        let round_stats = simulate_batches(args, seed, num_batches..round_end);
+        hand_stats.combine(&round_stats)?;
+        num_batches = round_end;
+
+        let largest_stderr = hand_stats.get_largest_standard_error();
+        let stop_reason = if largest_stderr < target_stderr {
+            Some("Converged")
+        } else if num_batches >= max_batches {
+            Some("Stopped at the iteration cap")
+        } else if args
+            .max_seconds
+            .is_some_and(|max_seconds| start_time.elapsed().as_secs_f64() >= max_seconds)
+        {
+            Some("Stopped at the time cap")
+        } else {
+            None
+        };
+
+        if let Some(stop_reason) = stop_reason {
+            println!(
+                "{} after {} iterations, largest standard error {:.5}",
+                stop_reason,
+                num_batches as u64 * THOUSAND as u64,
+                largest_stderr
+            );
+            return Ok(num_batches);
         }
     }
 }
 
-// fn _get_combined_stats(
-//     all_stats: Vec<HashMap<String, [u32; NUM_STATS]>>,
-// ) -> HashMap<String, [u32; NUM_STATS]> {
-//     let mut combined_stats = HashMap::new();
-//     for record in all_stats {
-//         combined_stats.extend(record);
-//     }
-//     return combined_stats;
-// }
+/// Exits with a usage error if the deck can't deal `num_players` hands and a board once
+/// `num_dead_cards` are out of play.
+fn check_deck_can_deal(num_players: u32, num_dead_cards: usize) {
+    let num_cards_needed = num_players as usize * NUM_CARDS_IN_HAND + NUM_COMMUNITY_CARDS;
+    if num_cards_needed + num_dead_cards > NUM_CARDS_IN_DECK {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "{} players and a board need {} cards, {} dead cards leave only {}",
+                    num_players,
+                    num_cards_needed,
+                    num_dead_cards,
+                    NUM_CARDS_IN_DECK.saturating_sub(num_dead_cards)
+                ),
+            )
+            .exit();
+    }
+}
 
-fn simulate(num_players: u32, verbose: bool, hand_stats: &mut HashMap<String, [u32; NUM_STATS]>) {
-    let mut dealer = Dealer::new();
-    let (community_cards, player_cards) = dealer.deal(num_players);
+/// Exits with a usage error if any card is used more than once.
+fn check_cards_are_unique(cards: &[Card]) {
+    let mut seen_cards = HashSet::new();
+    for card in cards {
+        if !seen_cards.insert(card) {
+            Args::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("{} is used more than once", card),
+                )
+                .exit();
+        }
+    }
+}
 
-    let player_cards_strings = player_cards
-        .iter()
-        .map(|cards| match verbose {
-            true => Card::cards_to_single_string(cards),
-            false => Card::get_shorten_hand_string(cards),
-        })
-        .collect::<Vec<String>>();
-
-    for player_cards_string in player_cards_strings {
-        // hand_stats.entry(player_cards_string).or_insert([0, 0]);
-        match hand_stats.entry(player_cards_string) {
-            std::collections::hash_map::Entry::Occupied(mut entry) => {
-                let counts = entry.get_mut();
-                counts[Stats::NumberOfHands.get_index()] += 1;
-            }
-            std::collections::hash_map::Entry::Vacant(entry) => {
-                let mut counts = [0; NUM_STATS];
-                counts[Stats::NumberOfHands.get_index()] = 1;
-                entry.insert(counts);
+/// Removes the combos holding a known card, exits with a usage error if none are left.
+fn remove_blocked_combos(range: &mut Range, known_cards: &[Card]) {
+    let range_str = range.to_string();
+    range.remove_blocked(known_cards);
+    if range.is_empty() {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!("every combo of \"{}\" holds a known card", range_str),
+            )
+            .exit();
+    }
+}
+
+/// Exits with a usage error if the ranges of the lineup can't be dealt without sharing a card.
+fn check_ranges_can_be_dealt(lineup: &Lineup, seed: u64) {
+    let mut dealer = lineup.new_dealer(Dealer::seeded_rng(seed, 0));
+    if !dealer.can_deal_from_ranges(&lineup.ranges) {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "the ranges can't be dealt together without sharing a card",
+            )
+            .exit();
+    }
+}
+
+/// Plays the lineup `num_batches` thousand times and returns the combined stats of every seat.
+fn run_lineup(
+    lineup: &Lineup,
+    num_batches: u32,
+    num_threads: u32,
+    evaluator: Evaluator,
+    seed: u64,
+) -> Result<LineupStats, RangeDealError> {
+    let thread_lineup_stats = run_on_threads(num_threads, 0..num_batches, |batches| {
+        let mut lineup_stats = LineupStats::new(lineup.num_players);
+        for batch in batches {
+            let mut dealer = lineup.new_dealer(Dealer::seeded_rng(seed, batch));
+            for _ in 0..THOUSAND {
+                equity::simulate_lineup(&mut dealer, lineup, evaluator, &mut lineup_stats)?;
             }
         }
+        Ok(lineup_stats)
+    });
+
+    let mut lineup_stats = LineupStats::new(lineup.num_players);
+    for thread_stats in thread_lineup_stats {
+        lineup_stats.combine(&thread_stats?);
     }
+    return Ok(lineup_stats);
+}
 
-    // println!("Community Cards:");
-    // println!("    {:?}", Card::cards_to_string(&community_cards));
-    // println!("Player Cards:");
-    // for (index, player_hand) in player_cards.iter().enumerate() {
-    //     println!("    player {}:", { index });
-    //     println!("        hand: {:?}", Card::cards_to_string(player_hand))
-    // }
+/// Exits with an error if the ranges of the lineup could not be dealt during a run.
+fn exit_on_range_deal_error<T>(result: Result<T, RangeDealError>) -> T {
+    return result.unwrap_or_else(|err| {
+        eprintln!("Error: {}, try wider ranges", err);
+        process::exit(1);
+    });
+}
 
-    let analyser = HandAnalyser::new(community_cards, player_cards);
-    let winning_hands = analyser.get_winning_hands(verbose);
-    for (hand, _hand_type) in winning_hands {
-        match hand_stats.entry(hand) {
-            std::collections::hash_map::Entry::Occupied(mut entry) => {
-                let counts = entry.get_mut();
-                counts[1] += 1;
-            }
-            std::collections::hash_map::Entry::Vacant(entry) => {
-                let mut counts = [0; NUM_STATS];
-                counts[Stats::NumberOfHands.get_index()] = 1;
-                counts[Stats::NumberOfWins.get_index()] = 1;
-                entry.insert(counts);
+fn print_known_cards(lineup: &Lineup) {
+    if !lineup.board.is_empty() {
+        println!("Board: {}", Card::cards_to_notation(&lineup.board));
+    }
+    if !lineup.dead_cards.is_empty() {
+        println!(
+            "Dead cards: {}",
+            Card::cards_to_notation(&lineup.dead_cards)
+        );
+    }
+}
+
+fn run_hero(args: &Args, hero_cards: &[Card], seed: u64) {
+    if args.villains.len() as u32 >= args.num_players {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "{} villain ranges need more than {} players",
+                    args.villains.len(),
+                    args.num_players
+                ),
+            )
+            .exit();
+    }
+
+    check_deck_can_deal(
+        args.num_players,
+        args.dead.as_ref().map_or(0, |dead| dead.len()),
+    );
+
+    let mut lineup = Lineup {
+        num_players: args.num_players,
+        fixed_hands: vec![hero_cards.to_vec()],
+        ranges: args.villains.clone(),
+        board: args.board.clone().unwrap_or_default(),
+        dead_cards: args.dead.clone().unwrap_or_default(),
+    };
+    let known_cards = lineup.get_known_cards();
+    check_cards_are_unique(&known_cards);
+    for range in lineup.ranges.iter_mut() {
+        remove_blocked_combos(range, &known_cards);
+    }
+    check_ranges_can_be_dealt(&lineup, seed);
+
+    let seat_stats = exit_on_range_deal_error(run_lineup(
+        &lineup,
+        args.num_thousand_iterations,
+        args.threads,
+        args.evaluator,
+        seed,
+    ))
+    .seats;
+
+    println!(
+        "Hero: {} ({}) vs {} opponents",
+        StartingHand::from_cards(hero_cards),
+        Card::cards_to_notation(hero_cards),
+        args.num_players - 1
+    );
+    print_known_cards(&lineup);
+    if !lineup.board.is_empty() {
+        let hero_rank = HandRank::evaluate(&[hero_cards, &lineup.board].concat());
+        println!("Hero has {}", hero_rank.get_hand_name());
+    }
+    println!("Iterations: {}", seat_stats[HERO_SEAT].iterations);
+    println!("Seed: {}", seed);
+    for line in seat_stats[HERO_SEAT].to_report_lines() {
+        println!("{}", line);
+    }
+    for (seat, stats) in seat_stats.iter().enumerate().skip(HERO_SEAT + 1) {
+        let seat_hands = match lineup.ranges.get(seat - HERO_SEAT - 1) {
+            Some(range) => format!("{} combos: {}", range.combos().len(), range),
+            None => "random".to_string(),
+        };
+        println!(
+            "Seat {} ({}) {}",
+            seat + 1,
+            seat_hands,
+            stats.to_report_lines().join(", ")
+        );
+    }
+}
+
+/// Plays a fully known lineup on every remaining board and returns the exact stats of every seat.
+fn enumerate_lineup(lineup: &Lineup, num_threads: u32, evaluator: Evaluator) -> LineupStats {
+    // the dealer's random number generator is never used when enumerating
+    let dealer = lineup.new_dealer(Dealer::seeded_rng(0, 0));
+    let num_boards = dealer.count_board_completions();
+    // each part is a contiguous run of boards, so no thread builds the boards of another
+    let thread_lineup_stats = run_on_threads(num_threads, 0..num_threads, |parts| {
+        let mut lineup_stats = LineupStats::new(lineup.num_players);
+        for part in parts {
+            let first_board = num_boards * part / num_threads as u64;
+            let end_board = num_boards * (part + 1) / num_threads as u64;
+            equity::enumerate_lineup(
+                &dealer,
+                lineup,
+                evaluator,
+                first_board..end_board,
+                &mut lineup_stats,
+            );
+        }
+        lineup_stats
+    });
+
+    let mut lineup_stats = LineupStats::new(lineup.num_players);
+    for thread_stats in &thread_lineup_stats {
+        lineup_stats.combine(thread_stats);
+    }
+    return lineup_stats;
+}
+
+fn run_equity(
+    ranges: &[Range],
+    board: &[Card],
+    dead_cards: &[Card],
+    num_thousand_iterations: u32,
+    exact_threshold: u64,
+    args: &Args,
+    seed: u64,
+) {
+    // a single combo range is a known hand, so it blocks combos of the other ranges
+    let single_hands = ranges
+        .iter()
+        .filter(|range| range.combos().len() == 1)
+        .map(|range| range.combos()[0].cards.clone())
+        .collect::<Vec<Vec<Card>>>();
+    let known_cards = [single_hands.concat(), board.to_vec(), dead_cards.to_vec()].concat();
+    check_cards_are_unique(&known_cards);
+    check_deck_can_deal(ranges.len() as u32, dead_cards.len());
+
+    let is_every_hand_known = single_hands.len() == ranges.len();
+    let mut lineup = Lineup {
+        num_players: ranges.len() as u32,
+        fixed_hands: Vec::new(),
+        ranges: Vec::new(),
+        board: board.to_vec(),
+        dead_cards: dead_cards.to_vec(),
+    };
+    if is_every_hand_known {
+        lineup.fixed_hands = single_hands;
+    } else {
+        lineup.ranges = ranges.to_vec();
+        for range in lineup.ranges.iter_mut() {
+            if range.combos().len() > 1 {
+                remove_blocked_combos(range, &known_cards);
             }
         }
+        check_ranges_can_be_dealt(&lineup, seed);
+    }
+
+    let num_boards = lineup
+        .new_dealer(Dealer::seeded_rng(seed, 0))
+        .count_board_completions();
+    if num_boards == 0 {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "the known cards leave no board to deal",
+            )
+            .exit();
+    }
+    let is_exact = is_every_hand_known && num_boards <= exact_threshold;
+    let lineup_stats = if is_exact {
+        enumerate_lineup(&lineup, args.threads, args.evaluator)
+    } else {
+        exit_on_range_deal_error(run_lineup(
+            &lineup,
+            num_thousand_iterations,
+            args.threads,
+            args.evaluator,
+            seed,
+        ))
+    };
+
+    print_known_cards(&lineup);
+    if is_exact {
+        println!("Exact: every one of {} boards", num_boards);
+    } else {
+        println!("Iterations: {}", lineup_stats.seats[HERO_SEAT].iterations);
+        println!("Seed: {}", seed);
+    }
+    for (range, stats) in ranges.iter().zip(&lineup_stats.seats) {
+        println!("{} {}", range, stats.to_report_lines().join(", "));
+    }
+
+    if lineup_stats.first_seat_combos.len() > 1 {
+        println!("{} by combo:", ranges[HERO_SEAT]);
+        let mut combo_stats = lineup_stats
+            .first_seat_combos
+            .iter()
+            .map(|(combo_index, stats)| {
+                (
+                    Card::cards_to_notation(&Card::combo_from_index(*combo_index)),
+                    stats,
+                )
+            })
+            .collect::<Vec<(String, &EquityStats)>>();
+        combo_stats.sort_by(|(combo_1, stats_1), (combo_2, stats_2)| {
+            stats_2
+                .get_equity()
+                .total_cmp(&stats_1.get_equity())
+                .then(combo_1.cmp(combo_2))
+        });
+        for (combo, stats) in combo_stats {
+            println!(
+                "{} ({} deals) {}",
+                combo,
+                stats.iterations,
+                stats.to_report_lines().join(", ")
+            );
+        }
+    }
+}
+
+/// Prints every hand from the highest win rate to the lowest. Hands marked with "*" have a 95%
+/// interval overlapping the next hand's, so the order of the two is not settled yet.
+fn print_ranking(hand_stats: &HandStatsTable) {
+    let ranked_hands = hand_stats.rank_hands();
+    println!(
+        "{:>4}  {:<28} {:>8} {:>9}  95% interval",
+        "Rank", "Hand", "Win rate", "Std error"
+    );
+    for (index, (hand, stats)) in ranked_hands.iter().enumerate() {
+        let (low, high) = stats.get_confidence_interval();
+        let overlaps_next = ranked_hands
+            .get(index + 1)
+            .is_some_and(|(_, next_stats)| stats.overlaps(next_stats));
+        println!(
+            "{:>4}  {:<28} {:>8.4} {:>9.4}  {:.4} - {:.4}{}",
+            index + 1,
+            hand,
+            stats.get_equity(),
+            stats.get_standard_error(),
+            low,
+            high,
+            if overlaps_next { " *" } else { "" }
+        );
+    }
+    println!("* overlaps the next hand's interval");
+}
+
+fn get_canonical_path(file: &FileIO) -> PathBuf {
+    return fs::canonicalize(file.get_file_path())
+        .unwrap_or_else(|_| PathBuf::from(file.get_file_path()));
+}
+
+/// Adds up the result files into `output_file` and returns the merged metadata and number of
+/// unique hands. Errors name the file that cannot be read or does not match the others.
+fn merge_files(
+    num_players: u32,
+    verbose: bool,
+    files: &[FileIO],
+    output_file: &FileIO,
+) -> Result<(FileMetadata, usize), Box<dyn Error>> {
+    // a file merged twice would count its runs twice
+    let mut merged_paths = HashSet::new();
+    for file in files {
+        if !Path::new(file.get_file_path()).exists() {
+            return Err(format!("{} does not exist", file.get_file_path()).into());
+        }
+        if !merged_paths.insert(get_canonical_path(file)) {
+            return Err(format!("{} is merged more than once", file.get_file_path()).into());
+        }
+    }
+    // every file is locked once, the output may be one of the merged files
+    let mut _locks = files
+        .iter()
+        .map(|file| file.lock())
+        .collect::<Result<Vec<File>, Box<dyn Error>>>()?;
+    if !merged_paths.contains(&get_canonical_path(output_file)) {
+        _locks.push(output_file.lock()?);
+    }
+
+    let mut merged_stats = HandStatsTable::new(verbose);
+    let mut merged_metadata: Option<FileMetadata> = None;
+    for file in files {
+        let with_path = |err: Box<dyn Error>| format!("{}: {}", file.get_file_path(), err);
+        let (hand_stats, metadata) = file
+            .read_from_file(num_players, verbose)
+            .map_err(with_path)?;
+        match &mut merged_metadata {
+            Some(merged_metadata) => merged_metadata.combine(&metadata).map_err(with_path)?,
+            None => merged_metadata = Some(metadata),
+        }
+        merged_stats
+            .combine(&hand_stats)
+            .map_err(|err| with_path(err.into()))?;
+    }
+
+    let merged_metadata = merged_metadata.ok_or("No files to merge")?;
+    output_file.write_to_file(&merged_metadata, &merged_stats)?;
+    return Ok((merged_metadata, merged_stats.num_dealt_hands()));
+}
+
+fn run_merge(num_players: u32, verbose: bool, file_paths: &[String], output_path: &str) {
+    let files = file_paths
+        .iter()
+        .map(|file_path| FileIO::new(file_path.clone()))
+        .collect::<Vec<FileIO>>();
+    let output_file = FileIO::new(output_path.to_string());
+    match merge_files(num_players, verbose, &files, &output_file) {
+        Ok((metadata, num_unique_hands)) => println!(
+            "Merged {} files into {}: {} iterations, {} unique hands",
+            files.len(),
+            output_path,
+            metadata.num_iterations,
+            num_unique_hands
+        ),
+        Err(err) => {
+            eprintln!("Error: {}, {} was left unchanged", err, output_path);
+            process::exit(1);
+        }
     }
 }
 
@@ -117,6 +637,55 @@ fn main() {
     let args = Args::parse();
     let logger = Logger::new(args.debug);
 
+    let seed = args.seed.unwrap_or_else(rand::random);
+    logger.print(format!("Seed: {}", seed));
+
+    match &args.command {
+        Some(Command::Bench { num_thousand_hands }) => {
+            let num_hands = u64::from(*num_thousand_hands)
+                .checked_mul(THOUSAND as u64)
+                .unwrap_or_else(|| {
+                    eprintln!("Error: {} thousand hands is too many", num_thousand_hands);
+                    process::exit(1);
+                });
+            benchmark::run_benchmark(num_hands, seed);
+            return;
+        }
+        Some(Command::Equity {
+            ranges,
+            board,
+            dead,
+            num_thousand_iterations,
+            exact_threshold,
+        }) => {
+            run_equity(
+                ranges,
+                board.as_deref().unwrap_or_default(),
+                dead.as_deref().unwrap_or_default(),
+                *num_thousand_iterations,
+                *exact_threshold,
+                &args,
+                seed,
+            );
+            return;
+        }
+        Some(Command::Merge {
+            num_players,
+            files,
+            output,
+            verbose,
+        }) => {
+            run_merge(*num_players, *verbose, files, output);
+            return;
+        }
+        None => {}
+    }
+
+    if let Some(hero_cards) = &args.hero {
+        run_hero(&args, hero_cards, seed);
+        return;
+    }
+
     let verbose_str = if args.verbose { "_verbose" } else { "" };
 
     let file = FileIO::new(format!(
@@ -124,31 +693,63 @@ fn main() {
         args.num_players, verbose_str
     ));
 
-    let (mut hand_stats, mut total_stats) = file.read_from_file().expect("Error reading from file");
-
-    for _ in 0..args.num_thousand_iterations * THOUSAND {
-        simulate(args.num_players, args.verbose, &mut hand_stats)
+    // held until the results are written, so no other run reads the file in the meantime
+    let _lock = file.lock().unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    });
+    let (mut hand_stats, mut metadata) = file
+        .read_from_file(args.num_players, args.verbose)
+        .expect("Error reading from file");
+    // checked before running, as the run would be refused when its results are added
+    if let Err(err) = metadata.check_seeds_are_new(&[seed]) {
+        eprintln!(
+            "Error: {} ({}), run again with another seed",
+            err,
+            file.get_file_path()
+        );
+        process::exit(1);
     }
 
-    total_stats[TotalStats::NumberOfIterations.get_index()] +=
-        args.num_thousand_iterations * THOUSAND;
-    total_stats[TotalStats::NumberOfUniqueHands.get_index()] =
-        hand_stats.keys().len().try_into().unwrap();
-
-    file.clear_file().unwrap();
+    let num_batches = match args.target_stderr {
+        Some(target_stderr) => run_until_converged(&args, seed, target_stderr, &mut hand_stats),
+        None => {
+            let batches = 0..args.num_thousand_iterations;
+            hand_stats
+                .combine(&simulate_batches(&args, seed, batches))
+                .map(|_| args.num_thousand_iterations)
+        }
+    };
+    // the file is only rewritten once every count is known to fit
+    let added_run = num_batches
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|num_batches| metadata.add_run(seed, num_batches as u64 * THOUSAND as u64));
+    if let Err(err) = added_run {
+        eprintln!(
+            "Error: {}, {} was left unchanged",
+            err,
+            file.get_file_path()
+        );
+        process::exit(1);
+    }
 
     logger.print(format!(
         "Number of unique hands: {}",
-        &hand_stats.keys().len()
+        hand_stats.num_dealt_hands()
     ));
+    for (hand, stats) in hand_stats.dealt_hands() {
+        logger.print(format!(
+            "{}, {:?}, equity: {:.4}",
+            hand,
+            stats.counts,
+            stats.get_equity()
+        ));
+    }
 
-    file.write_to_file(format!("{}", FileIO::array_to_string(&total_stats)))
-        .unwrap();
+    file.write_to_file(&metadata, &hand_stats).unwrap();
 
-    for (hand, counts) in &hand_stats {
-        logger.print(format!("{}, {:?}", hand, counts));
-        file.write_to_file(format!("{},{}", hand, FileIO::array_to_string(counts)))
-            .unwrap();
+    if args.ranking {
+        print_ranking(&hand_stats);
     }
 
     // let mut community_cards = vec![];
@@ -173,6 +774,276 @@ fn main() {
     //     },
     // ]];
 
-    // let analyser = HandAnalyser::new(community_cards, player_cards);
+    // let analyser = HandAnalyser::new(community_cards, player_cards, evaluator);
     // analyser.get_winning_hand();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stats::Stats;
+
+    fn parse_args(args: &[&str]) -> Args {
+        return Args::parse_from(std::iter::once("poker_calculator").chain(args.iter().copied()));
+    }
+
+    #[test]
+    fn batches_give_the_same_stats_on_any_number_of_threads() {
+        let one_thread = simulate_batches(&parse_args(&["6", "-t", "1"]), 9, 0..6);
+        let three_threads = simulate_batches(&parse_args(&["6", "-t", "3"]), 9, 0..6);
+        assert_eq!(one_thread, three_threads);
+        assert_ne!(one_thread, simulate_batches(&parse_args(&["6"]), 10, 0..6));
+    }
+
+    #[test]
+    fn per_thread_stats_add_up_to_the_whole_run() {
+        let args = parse_args(&["4", "-t", "3"]);
+        let mut hand_stats = HandStatsTable::new(false);
+        for batches in [0..1, 1..2, 2..5] {
+            hand_stats
+                .combine(&simulate_batches(&args, 5, batches))
+                .unwrap();
+        }
+        assert_eq!(hand_stats, simulate_batches(&args, 5, 0..5));
+
+        let dealt_hands = hand_stats.dealt_hands();
+        let num_hands = dealt_hands
+            .iter()
+            .map(|(_, stats)| stats.get(Stats::NumberOfHands))
+            .sum::<u64>();
+        assert_eq!(num_hands, 5 * THOUSAND as u64 * 4);
+        let num_pots = dealt_hands
+            .iter()
+            .map(|(_, stats)| stats.get_pot_share())
+            .sum::<f64>();
+        assert!((num_pots - 5.0 * THOUSAND as f64).abs() < 1e-6);
+    }
+
+    #[test]
+    fn lineup_equity_on_a_fixed_board_matches_the_outs() {
+        // aces only beat the set of kings on the two remaining aces
+        let lineup = Lineup {
+            num_players: 2,
+            fixed_hands: vec![
+                Card::parse_cards("AhAd").unwrap(),
+                Card::parse_cards("KcKs").unwrap(),
+            ],
+            ranges: Vec::new(),
+            board: Card::parse_cards("Kh7c2d5s").unwrap(),
+            dead_cards: Vec::new(),
+        };
+        for num_threads in [1, 3] {
+            let lineup_stats = enumerate_lineup(&lineup, num_threads, Evaluator::Bitmask);
+            let aces = &lineup_stats.seats[0];
+            assert_eq!((aces.iterations, aces.wins, aces.ties), (44, 2, 0));
+            assert_eq!(lineup_stats.seats[1].wins, 42);
+            assert_eq!(aces.get_equity(), 2.0 / 44.0);
+        }
+
+        let lineup_stats = run_lineup(&lineup, 2, 1, Evaluator::Grouping, 3).unwrap();
+        let aces = &lineup_stats.seats[0];
+        assert_eq!(aces.iterations, 2 * THOUSAND as u64);
+        assert_eq!(aces.ties, 0);
+        assert!((aces.get_equity() - 2.0 / 44.0).abs() < 0.02);
+        let aces_combo = Card::get_combo_index(&lineup.fixed_hands[0]);
+        assert_eq!(lineup_stats.first_seat_combos[&aces_combo].wins, aces.wins);
+    }
+
+    #[test]
+    fn chopped_lineup_pots_add_up_the_same_on_any_number_of_threads() {
+        let lineup = Lineup {
+            num_players: 2,
+            fixed_hands: vec![
+                Card::parse_cards("AsKc").unwrap(),
+                Card::parse_cards("AdKh").unwrap(),
+            ],
+            ranges: Vec::new(),
+            board: Card::parse_cards("2c7d9h").unwrap(),
+            dead_cards: Vec::new(),
+        };
+        let one_thread = enumerate_lineup(&lineup, 1, Evaluator::Bitmask);
+        for num_threads in [2, 5] {
+            assert_eq!(
+                enumerate_lineup(&lineup, num_threads, Evaluator::Bitmask),
+                one_thread
+            );
+        }
+        let (ace_king, other_ace_king) = (&one_thread.seats[0], &one_thread.seats[1]);
+        assert_eq!(ace_king.split_pots[1], ace_king.ties);
+        assert_eq!(ace_king.wins, other_ace_king.losses);
+        assert_eq!(ace_king.get_equity() + other_ace_king.get_equity(), 1.0);
+    }
+
+    #[test]
+    fn converging_stops_at_the_target_or_a_cap() {
+        let converge = |args: &[&str]| {
+            let args = parse_args(args);
+            let mut hand_stats = HandStatsTable::new(false);
+            let num_batches =
+                run_until_converged(&args, 1, args.target_stderr.unwrap(), &mut hand_stats)
+                    .unwrap();
+            (num_batches, hand_stats)
+        };
+
+        let (num_batches, hand_stats) = converge(&["2", "--target-stderr", "0.5"]);
+        assert_eq!(num_batches, CONVERGENCE_CHECK_BATCHES);
+        assert!(hand_stats.get_largest_standard_error() < 0.5);
+
+        let (num_batches, hand_stats) = converge(&[
+            "2",
+            "--target-stderr",
+            "0.0001",
+            "--max-thousand-iterations",
+            "3",
+        ]);
+        assert_eq!(num_batches, 3);
+        assert_eq!(hand_stats, simulate_batches(&parse_args(&["2"]), 1, 0..3));
+
+        let (num_batches, _) = converge(&["2", "--target-stderr", "0.0001", "--max-seconds", "0"]);
+        assert_eq!(num_batches, CONVERGENCE_CHECK_BATCHES);
+    }
+
+    #[test]
+    fn losses_are_recorded_against_the_winning_category() {
+        // a nut flush and two pair both lose to twos full of queens
+        let mut hand_stats = HandStatsTable::new(false);
+        record_deal(
+            Card::parse_cards("Qh7h2hQc7d").unwrap(),
+            vec![
+                Card::parse_cards("Ah3h").unwrap(),
+                Card::parse_cards("2s2c").unwrap(),
+                Card::parse_cards("9c8c").unwrap(),
+            ],
+            &[],
+            Evaluator::Bitmask,
+            &mut hand_stats,
+        );
+
+        let flush = HandRank::evaluate(&Card::parse_cards("Qh7h2hQc7dAh3h").unwrap());
+        let full_house = HandRank::evaluate(&Card::parse_cards("Qh7h2hQc7d2s2c").unwrap());
+        let two_pair = HandRank::evaluate(&Card::parse_cards("Qh7h2hQc7d9c8c").unwrap());
+        let get_stats = |hand_stats: &mut HandStatsTable, cards: &str| {
+            let hand_index = hand_stats.get_hand_index(&Card::parse_cards(cards).unwrap());
+            hand_stats.get_mut(hand_index).clone()
+        };
+        let ace_three = get_stats(&mut hand_stats, "Ah3h");
+        assert_eq!(
+            ace_three.loss_matrix[flush.get_category()][full_house.get_category()],
+            1
+        );
+        let nine_eight = get_stats(&mut hand_stats, "9c8c");
+        assert_eq!(
+            nine_eight.loss_matrix[two_pair.get_category()][full_house.get_category()],
+            1
+        );
+        for stats in [&ace_three, &nine_eight] {
+            assert_eq!(stats.loss_matrix.as_flattened().iter().sum::<u64>(), 1);
+        }
+        let twos = get_stats(&mut hand_stats, "2s2c");
+        assert_eq!(twos.loss_matrix.as_flattened().iter().sum::<u64>(), 0);
+        assert_eq!(twos.win_categories[full_house.get_category()], 1);
+    }
+
+    #[test]
+    fn streets_record_who_led_and_whether_the_flop_lead_held() {
+        // the nut flush leads on the flop, twos full of queens take the lead on the turn
+        let community_cards = Card::parse_cards("Qh7h2hQc7d").unwrap();
+        let player_cards = ["Ah3h", "2s2c", "9c8c"]
+            .map(|cards| Card::parse_cards(cards).unwrap())
+            .to_vec();
+        let street_ranks = STREETS.map(|street| {
+            let street_cards = &community_cards[..street.get_num_community_cards()];
+            HandAnalyser::new(street_cards, &player_cards, Evaluator::Bitmask).get_hand_ranks()
+        });
+        let mut hand_stats = HandStatsTable::new(false);
+        record_deal(
+            community_cards,
+            player_cards,
+            &street_ranks,
+            Evaluator::Bitmask,
+            &mut hand_stats,
+        );
+
+        let mut get_stats = |cards: &str| {
+            let hand_index = hand_stats.get_hand_index(&Card::parse_cards(cards).unwrap());
+            hand_stats.get_mut(hand_index).clone()
+        };
+        let (ace_three, twos, nine_eight) =
+            (get_stats("Ah3h"), get_stats("2s2c"), get_stats("9c8c"));
+        assert_eq!(
+            (ace_three.street_leads, ace_three.flop_leads_won),
+            ([1, 0], 0)
+        );
+        assert_eq!((twos.street_leads, twos.flop_leads_won), ([0, 1], 0));
+        assert_eq!(nine_eight.street_leads, [0, 0]);
+        let flush = street_ranks[Street::Flop.get_index()][0];
+        let set = street_ranks[Street::Flop.get_index()][1];
+        assert_eq!(
+            ace_three.street_categories[Street::Flop.get_index()][flush.get_category()],
+            1
+        );
+        assert_eq!(
+            twos.street_categories[Street::Flop.get_index()][set.get_category()],
+            1
+        );
+    }
+
+    #[test]
+    fn simulating_streets_records_every_seat_on_every_street() {
+        let num_deals = 500;
+        let mut dealer = Dealer::new(Dealer::seeded_rng(2, 0));
+        let mut hand_stats = HandStatsTable::new(false);
+        for _ in 0..num_deals {
+            simulate(&mut dealer, 5, Evaluator::Bitmask, true, &mut hand_stats);
+        }
+        let dealt_hands = hand_stats.dealt_hands();
+        for street in STREETS {
+            let num_street_hands = dealt_hands
+                .iter()
+                .map(|(_, stats)| {
+                    stats.street_categories[street.get_index()]
+                        .iter()
+                        .sum::<u64>()
+                })
+                .sum::<u64>();
+            assert_eq!(num_street_hands, num_deals * 5);
+            let num_leads = dealt_hands
+                .iter()
+                .map(|(_, stats)| stats.street_leads[street.get_index()])
+                .sum::<u64>();
+            assert!(num_leads >= num_deals);
+        }
+        for (_, stats) in &dealt_hands {
+            assert!(stats.flop_leads_won <= stats.street_leads[Street::Flop.get_index()]);
+        }
+
+        let mut untracked_stats = HandStatsTable::new(false);
+        simulate(
+            &mut dealer,
+            5,
+            Evaluator::Bitmask,
+            false,
+            &mut untracked_stats,
+        );
+        for (_, stats) in untracked_stats.dealt_hands() {
+            assert_eq!(stats.street_leads, [0, 0]);
+            assert_eq!(
+                stats.street_categories.as_flattened().iter().sum::<u64>(),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn ranges_that_cannot_be_dealt_fail_the_run_instead_of_a_thread() {
+        let aces: Range = "AA".parse().unwrap();
+        let lineup = Lineup {
+            num_players: 3,
+            fixed_hands: Vec::new(),
+            ranges: vec![aces.clone(), aces.clone(), aces],
+            board: Vec::new(),
+            dead_cards: Vec::new(),
+        };
+        assert!(run_lineup(&lineup, 3, 3, Evaluator::Bitmask, 1).is_err());
+    }
+}
@@ -0,0 +1,443 @@
+use super::card::{Card, CardNumber, CardParseError, StartingHand, TWO};
+use rand::Rng;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+const ACE_VALUE: u32 = 14;
+const FULL_WEIGHT: f64 = 1.0;
+const HAND_SEPARATOR: char = ',';
+const WEIGHT_SEPARATOR: char = ':';
+const SPAN_SEPARATOR: char = '-';
+const AND_ABOVE_SUFFIX: char = '+';
+const NUM_HOLE_CARDS: usize = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum RangeParseError {
+    Card(CardParseError),
+    InvalidWeight(String),
+    InvalidSpan(String),
+    Empty,
+}
+
+impl fmt::Display for RangeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Card(err) => write!(f, "{}", err),
+            Self::InvalidWeight(weight_str) => write!(
+                f,
+                "\"{}\" is not a weight, expected a number above 0 and at most 1",
+                weight_str
+            ),
+            Self::InvalidSpan(span_str) => write!(
+                f,
+                "\"{}\" is not a span of hands, expected e.g. \"99-66\" or \"A5s-A2s\"",
+                span_str
+            ),
+            Self::Empty => write!(f, "the range has no hands"),
+        }
+    }
+}
+
+impl Error for RangeParseError {}
+
+impl From<CardParseError> for RangeParseError {
+    fn from(err: CardParseError) -> Self {
+        return Self::Card(err);
+    }
+}
+
+/// One two card hand of a range and how often it is played, from just above 0 up to 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedCombo {
+    pub cards: Vec<Card>,
+    pub weight: f64,
+}
+
+/// A weighted set of two card hands written like "QQ+, AKs, A5s-A2s, KQo, 76s:0.5".
+#[derive(Debug, Clone)]
+pub struct Range {
+    combos: Vec<WeightedCombo>,
+    cumulative_weights: Vec<f64>,
+}
+
+impl Range {
+    fn new(combos: Vec<WeightedCombo>) -> Self {
+        let cumulative_weights = combos
+            .iter()
+            .scan(0.0, |total_weight, combo| {
+                *total_weight += combo.weight;
+                Some(*total_weight)
+            })
+            .collect();
+        Self {
+            combos,
+            cumulative_weights,
+        }
+    }
+
+    pub fn combos(&self) -> &[WeightedCombo] {
+        return &self.combos;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.combos.is_empty();
+    }
+
+    /// Removes every combo holding one of `known_cards`, e.g. the board or another player's hand.
+    pub fn remove_blocked(&mut self, known_cards: &[Card]) {
+        let mut combos = std::mem::take(&mut self.combos);
+        combos.retain(|combo| combo.cards.iter().all(|card| !known_cards.contains(card)));
+        *self = Range::new(combos);
+    }
+
+    /// Picks a random combo, each combo is picked in proportion to its weight.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> &[Card] {
+        let total_weight = self.cumulative_weights.last().expect("Range is empty");
+        let target_weight = rng.gen_range(0.0..*total_weight);
+        let index = self
+            .cumulative_weights
+            .partition_point(|cumulative_weight| *cumulative_weight <= target_weight);
+        return &self.combos[index.min(self.combos.len() - 1)].cards;
+    }
+
+    fn parse_weight(weight_str: &str) -> Result<f64, RangeParseError> {
+        let invalid_weight = || RangeParseError::InvalidWeight(weight_str.to_string());
+        let weight = weight_str
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| invalid_weight())?;
+        if !(weight > 0.0 && weight <= FULL_WEIGHT) {
+            return Err(invalid_weight());
+        }
+        return Ok(weight);
+    }
+
+    /// Parses "AK" as both "AKs" and "AKo", anything else as one starting hand.
+    fn parse_starting_hands(hand_str: &str) -> Result<Vec<StartingHand>, RangeParseError> {
+        let characters = hand_str.chars().collect::<Vec<char>>();
+        if characters.len() == 2 && characters[0] != characters[1] {
+            let suited = format!("{}s", hand_str).parse::<StartingHand>()?;
+            let off_suit = StartingHand::new(suited.high.clone(), suited.low.clone(), false);
+            return Ok(vec![suited, off_suit]);
+        }
+        return Ok(vec![hand_str.parse::<StartingHand>()?]);
+    }
+
+    /// The starting hands from `lowest` up to `highest`, pairs step both cards and other hands
+    /// step the low card.
+    fn starting_hands_between(
+        lowest: &StartingHand,
+        highest: &StartingHand,
+    ) -> Option<Vec<StartingHand>> {
+        let is_same_kind = lowest.is_pair() == highest.is_pair()
+            && lowest.suited == highest.suited
+            && (lowest.is_pair() || lowest.high == highest.high);
+        if !is_same_kind || lowest.low.get_value() > highest.low.get_value() {
+            return None;
+        }
+
+        let starting_hands = (lowest.low.get_value()..=highest.low.get_value())
+            .map(|low_value| {
+                let low = CardNumber::match_int_with_card_num(low_value);
+                let high = match lowest.is_pair() {
+                    true => low.clone(),
+                    false => lowest.high.clone(),
+                };
+                StartingHand::new(high, low, lowest.suited)
+            })
+            .collect();
+        return Some(starting_hands);
+    }
+
+    /// Parses one comma separated part of a range, without its weight.
+    fn parse_hand_group(group_str: &str) -> Result<Vec<Vec<Card>>, RangeParseError> {
+        if let Ok(cards) = Card::parse_cards(group_str) {
+            if cards.len() == NUM_HOLE_CARDS && cards[0] != cards[1] {
+                return Ok(vec![cards]);
+            }
+        }
+
+        let mut starting_hands = Vec::new();
+        if let Some(lowest_str) = group_str.strip_suffix(AND_ABOVE_SUFFIX) {
+            for lowest in Range::parse_starting_hands(lowest_str)? {
+                let highest = match lowest.is_pair() {
+                    true => {
+                        let ace = CardNumber::match_int_with_card_num(ACE_VALUE);
+                        StartingHand::new(ace.clone(), ace, false)
+                    }
+                    false => StartingHand::new(
+                        lowest.high.clone(),
+                        CardNumber::match_int_with_card_num(lowest.high.get_value() - 1),
+                        lowest.suited,
+                    ),
+                };
+                starting_hands.extend(
+                    Range::starting_hands_between(&lowest, &highest)
+                        .ok_or(RangeParseError::InvalidSpan(group_str.to_string()))?,
+                );
+            }
+        } else if let Some((highest_str, lowest_str)) = group_str.split_once(SPAN_SEPARATOR) {
+            let invalid_span = || RangeParseError::InvalidSpan(group_str.to_string());
+            let highest_hands = Range::parse_starting_hands(highest_str.trim())?;
+            let lowest_hands = Range::parse_starting_hands(lowest_str.trim())?;
+            if highest_hands.len() != lowest_hands.len() {
+                return Err(invalid_span());
+            }
+            for (highest, lowest) in highest_hands.iter().zip(&lowest_hands) {
+                starting_hands.extend(
+                    Range::starting_hands_between(lowest, highest).ok_or_else(invalid_span)?,
+                );
+            }
+        } else {
+            starting_hands = Range::parse_starting_hands(group_str)?;
+        }
+
+        return Ok(starting_hands
+            .iter()
+            .flat_map(|starting_hand| starting_hand.combos())
+            .collect());
+    }
+
+    /// Writes a run of starting hands of the same kind and weight, e.g. "QQ+", "A5s-A2s" or "KQo".
+    fn run_to_notation(run: &[StartingHand], reaches_top: bool) -> String {
+        let (highest, lowest) = (&run[0], &run[run.len() - 1]);
+        if run.len() == 1 {
+            return highest.to_string();
+        }
+        if reaches_top {
+            return format!("{}{}", lowest, AND_ABOVE_SUFFIX);
+        }
+        return format!("{}{}{}", highest, SPAN_SEPARATOR, lowest);
+    }
+
+    fn weight_to_notation(weight: f64) -> String {
+        if weight == FULL_WEIGHT {
+            return String::new();
+        }
+        return format!("{}{}", WEIGHT_SEPARATOR, weight);
+    }
+
+    /// Writes the range in compact notation, grouping complete starting hands of equal weight
+    /// into runs and listing the combos of incomplete starting hands one by one.
+    fn to_notation_parts(&self) -> Vec<String> {
+        let mut combos_by_hand: HashMap<StartingHand, Vec<&WeightedCombo>> = HashMap::new();
+        for combo in &self.combos {
+            combos_by_hand
+                .entry(StartingHand::from_cards(&combo.cards))
+                .or_default()
+                .push(combo);
+        }
+        let complete_hand_weight = |starting_hand: &StartingHand| -> Option<f64> {
+            let combos = combos_by_hand.get(starting_hand)?;
+            let weight = combos[0].weight;
+            let is_complete = combos.len() == starting_hand.combos().len()
+                && combos.iter().all(|combo| combo.weight == weight);
+            return is_complete.then_some(weight);
+        };
+
+        // every line holds the starting hands that can form one run, highest first
+        let mut lines: Vec<Vec<StartingHand>> = vec![(TWO..=ACE_VALUE)
+            .rev()
+            .map(|value| {
+                let number = CardNumber::match_int_with_card_num(value);
+                StartingHand::new(number.clone(), number, false)
+            })
+            .collect()];
+        for high_value in (TWO + 1..=ACE_VALUE).rev() {
+            for suited in [true, false] {
+                lines.push(
+                    (TWO..high_value)
+                        .rev()
+                        .map(|low_value| {
+                            StartingHand::new(
+                                CardNumber::match_int_with_card_num(high_value),
+                                CardNumber::match_int_with_card_num(low_value),
+                                suited,
+                            )
+                        })
+                        .collect(),
+                );
+            }
+        }
+
+        let mut notation_parts = Vec::new();
+        let mut incomplete_combos: Vec<&WeightedCombo> = Vec::new();
+        for line in &lines {
+            let mut index = 0;
+            while index < line.len() {
+                let Some(weight) = complete_hand_weight(&line[index]) else {
+                    if let Some(combos) = combos_by_hand.get(&line[index]) {
+                        incomplete_combos.extend(combos.iter());
+                    }
+                    index += 1;
+                    continue;
+                };
+                let run_start = index;
+                while index < line.len() && complete_hand_weight(&line[index]) == Some(weight) {
+                    index += 1;
+                }
+                notation_parts.push(format!(
+                    "{}{}",
+                    Range::run_to_notation(&line[run_start..index], run_start == 0),
+                    Range::weight_to_notation(weight)
+                ));
+            }
+        }
+        for combo in incomplete_combos {
+            notation_parts.push(format!(
+                "{}{}",
+                Card::cards_to_notation(&combo.cards),
+                Range::weight_to_notation(combo.weight)
+            ));
+        }
+        return notation_parts;
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_notation_parts().join(", "))
+    }
+}
+
+impl FromStr for Range {
+    type Err = RangeParseError;
+
+    /// A combo listed more than once keeps the weight it was given last.
+    fn from_str(range_str: &str) -> Result<Self, Self::Err> {
+        let mut combos: Vec<WeightedCombo> = Vec::new();
+        for group_str in range_str.split(HAND_SEPARATOR) {
+            let group_str = group_str.trim();
+            if group_str.is_empty() {
+                continue;
+            }
+            let (hands_str, weight) = match group_str.split_once(WEIGHT_SEPARATOR) {
+                Some((hands_str, weight_str)) => {
+                    (hands_str.trim(), Range::parse_weight(weight_str)?)
+                }
+                None => (group_str, FULL_WEIGHT),
+            };
+
+            for cards in Range::parse_hand_group(hands_str)? {
+                let same_combo = |combo: &WeightedCombo| {
+                    combo.cards == cards || combo.cards == [cards[1].clone(), cards[0].clone()]
+                };
+                match combos.iter_mut().find(|combo| same_combo(combo)) {
+                    Some(combo) => combo.weight = weight,
+                    None => combos.push(WeightedCombo { cards, weight }),
+                }
+            }
+        }
+
+        if combos.is_empty() {
+            return Err(RangeParseError::Empty);
+        }
+        return Ok(Range::new(combos));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn parse(range_str: &str) -> Range {
+        return range_str.parse().unwrap();
+    }
+
+    #[test]
+    fn expands_notation_into_combos() {
+        assert_eq!(parse("QQ+").combos().len(), 18);
+        assert_eq!(parse("AKs").combos().len(), 4);
+        assert_eq!(parse("KQo").combos().len(), 12);
+        assert_eq!(parse("AK").combos().len(), 16);
+        assert_eq!(parse("A5s-A2s").combos().len(), 16);
+        assert_eq!(parse("99-66").combos().len(), 24);
+        assert_eq!(parse("ATs+").combos().len(), 16);
+        assert_eq!(parse("AhKh").combos().len(), 1);
+        assert_eq!(parse("22+").combos().len(), 78);
+        assert_eq!(parse("QQ+, AKs, A5s-A2s, KQo, 76s:0.5").combos().len(), 54);
+    }
+
+    #[test]
+    fn keeps_weights() {
+        let range = parse("76s:0.5, AA");
+        let weights = range
+            .combos()
+            .iter()
+            .map(|combo| {
+                (
+                    StartingHand::from_cards(&combo.cards).to_string(),
+                    combo.weight,
+                )
+            })
+            .collect::<Vec<(String, f64)>>();
+        assert!(weights.contains(&("76s".to_string(), 0.5)));
+        assert!(weights.contains(&("AA".to_string(), 1.0)));
+
+        let overridden = parse("QQ+, AA:0.25");
+        assert_eq!(overridden.combos().len(), 18);
+        assert_eq!(overridden.to_string(), "AA:0.25, KK-QQ");
+    }
+
+    #[test]
+    fn rejects_invalid_notation() {
+        assert!(matches!(
+            "AKs:2".parse::<Range>(),
+            Err(RangeParseError::InvalidWeight(_))
+        ));
+        assert!(matches!(
+            "A5s-K2s".parse::<Range>(),
+            Err(RangeParseError::InvalidSpan(_))
+        ));
+        assert!(matches!(
+            "A2s-A5s".parse::<Range>(),
+            Err(RangeParseError::InvalidSpan(_))
+        ));
+        assert!(matches!(
+            "AXs".parse::<Range>(),
+            Err(RangeParseError::Card(_))
+        ));
+        assert_eq!(" , ".parse::<Range>().unwrap_err(), RangeParseError::Empty);
+    }
+
+    #[test]
+    fn prints_compact_notation() {
+        for range_str in [
+            "QQ+, AKs, A5s-A2s, KQo, 76s:0.5",
+            "22+",
+            "99-66, ATs+, KQo:0.75",
+            "AhKh",
+        ] {
+            assert_eq!(parse(range_str).to_string(), range_str);
+        }
+        assert_eq!(parse("AK").to_string(), "AKs, AKo");
+        assert_eq!(parse("KK, AA, QQ").to_string(), "QQ+");
+    }
+
+    #[test]
+    fn removes_blocked_combos() {
+        let mut range = parse("AA, AKs");
+        range.remove_blocked(&Card::parse_cards("Ah").unwrap());
+        assert_eq!(range.combos().len(), 3 + 3);
+        assert_eq!(range.to_string(), "AdAs, AdAc, AsAc, AdKd, AsKs, AcKc");
+
+        range.remove_blocked(&Card::parse_cards("AdAsAc").unwrap());
+        assert!(range.is_empty());
+    }
+
+    #[test]
+    fn samples_in_proportion_to_weight() {
+        let range = parse("AA, KK:0.5");
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let num_samples = 90_000;
+        let num_aces = (0..num_samples)
+            .filter(|_| range.sample(&mut rng)[0].number.get_value() == ACE_VALUE)
+            .count();
+        let expected_aces = num_samples as f64 * 6.0 / 9.0;
+        assert!((num_aces as f64 - expected_aces).abs() < 0.01 * num_samples as f64);
+    }
+}
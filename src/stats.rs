@@ -0,0 +1,638 @@
+use super::card::{Card, StartingHand, NUM_HOLE_CARD_COMBOS, NUM_STARTING_HANDS};
+use super::dealer::{Street, MAX_PLAYERS, NUM_STREETS, STREETS};
+use super::hand_analyser::{HandRank, HAND_CATEGORY_NAMES, NUM_HAND_CATEGORIES};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+pub const NUM_STATS: usize = 3;
+
+/// Two sided 95% quantile of the standard normal distribution.
+const Z_95: f64 = 1.96;
+
+/// Number of values in a legacy row, written before ties were tracked separately.
+const NUM_LEGACY_STATS: usize = 2;
+/// Number of count sections written before pots were counted by their number of winners.
+const NUM_SECTIONS_BEFORE_SPLIT_POTS: usize = 7;
+
+/// A count that would no longer fit in 64 bits, named by what was being counted.
+#[derive(Debug, PartialEq)]
+pub struct CountOverflowError(pub String);
+
+impl fmt::Display for CountOverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the count of {} overflowed", self.0)
+    }
+}
+
+impl Error for CountOverflowError {}
+
+#[allow(clippy::enum_variant_names)]
+pub enum Stats {
+    NumberOfHands,
+    NumberOfWins,
+    NumberOfTies,
+}
+
+impl Stats {
+    pub fn get_index(&self) -> usize {
+        match self {
+            Self::NumberOfHands => 0,
+            Self::NumberOfWins => 1,
+            Self::NumberOfTies => 2,
+        }
+    }
+}
+
+/// Counts for one starting hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HandStats {
+    /// Hands, outright wins and chopped pots, indexed by `Stats`.
+    pub counts: [u64; NUM_STATS],
+    /// Pot share of rows read from files written before pots were counted by their number of
+    /// winners, 0 for hands dealt since.
+    pub legacy_pot_share: f64,
+    /// Made hands at every showdown, by hand category.
+    pub showdown_categories: [u64; NUM_HAND_CATEGORIES],
+    /// Made hands of every pot won or chopped, by hand category.
+    pub win_categories: [u64; NUM_HAND_CATEGORIES],
+    /// Lost pots indexed by `[own category][winner's category]`.
+    pub loss_matrix: [[u64; NUM_HAND_CATEGORIES]; NUM_HAND_CATEGORIES],
+    /// Showdowns finished in each position, from 1st up to `MAX_PLAYERS`th. Tied seats share a
+    /// position.
+    pub finishing_positions: [u64; MAX_PLAYERS as usize],
+    /// Made hands on the flop and turn indexed by `[street][category]`.
+    pub street_categories: [[u64; NUM_HAND_CATEGORIES]; NUM_STREETS],
+    /// Streets the hand was leading on, alone or tied.
+    pub street_leads: [u64; NUM_STREETS],
+    /// Pots won or chopped at the river after leading on the flop.
+    pub flop_leads_won: u64,
+    /// Pots won indexed by the number of winners less one, from outright wins up to
+    /// `MAX_PLAYERS`-way chops. The pot share, where a k-way chop only adds 1/k, is worked out
+    /// from these whole numbers, so it never depends on the order counts were added in.
+    pub split_pots: [u64; MAX_PLAYERS as usize],
+    /// Hands whose made hand, finishing position and pot were counted, fewer than all the hands
+    /// once legacy rows holding only hands and wins are added.
+    pub detailed_hands: u64,
+}
+
+impl HandStats {
+    pub fn new() -> Self {
+        Self {
+            counts: [0; NUM_STATS],
+            legacy_pot_share: 0.0,
+            showdown_categories: [0; NUM_HAND_CATEGORIES],
+            win_categories: [0; NUM_HAND_CATEGORIES],
+            loss_matrix: [[0; NUM_HAND_CATEGORIES]; NUM_HAND_CATEGORIES],
+            finishing_positions: [0; MAX_PLAYERS as usize],
+            street_categories: [[0; NUM_HAND_CATEGORIES]; NUM_STREETS],
+            street_leads: [0; NUM_STREETS],
+            flop_leads_won: 0,
+            split_pots: [0; MAX_PLAYERS as usize],
+            detailed_hands: 0,
+        }
+    }
+
+    /// The counts written after the pot share, in the order they were added to the file format.
+    fn count_sections(&self) -> [&[u64]; 9] {
+        return [
+            &self.showdown_categories,
+            &self.win_categories,
+            self.loss_matrix.as_flattened(),
+            &self.finishing_positions,
+            self.street_categories.as_flattened(),
+            &self.street_leads,
+            std::slice::from_ref(&self.flop_leads_won),
+            &self.split_pots,
+            std::slice::from_ref(&self.detailed_hands),
+        ];
+    }
+
+    fn count_sections_mut(&mut self) -> [&mut [u64]; 9] {
+        return [
+            &mut self.showdown_categories,
+            &mut self.win_categories,
+            self.loss_matrix.as_flattened_mut(),
+            &mut self.finishing_positions,
+            self.street_categories.as_flattened_mut(),
+            &mut self.street_leads,
+            std::slice::from_mut(&mut self.flop_leads_won),
+            &mut self.split_pots,
+            std::slice::from_mut(&mut self.detailed_hands),
+        ];
+    }
+
+    /// Names of the values of `to_values`, in the same order.
+    pub fn get_column_names() -> Vec<String> {
+        let categories = HAND_CATEGORY_NAMES.map(|name| name.to_lowercase().replace(' ', "_"));
+        let mut column_names = ["hands", "wins", "ties", "pot_share"]
+            .map(String::from)
+            .to_vec();
+        for prefix in ["showdown", "win"] {
+            for category in &categories {
+                column_names.push(format!("{}_{}", prefix, category));
+            }
+        }
+        for own_category in &categories {
+            for winning_category in &categories {
+                column_names.push(format!("loss_{}_to_{}", own_category, winning_category));
+            }
+        }
+        for position in 1..=MAX_PLAYERS {
+            column_names.push(format!("finish_{}", position));
+        }
+        for street in STREETS {
+            for category in &categories {
+                column_names.push(format!("{}_{}", street.get_name(), category));
+            }
+        }
+        for street in STREETS {
+            column_names.push(format!("{}_leads", street.get_name()));
+        }
+        column_names.push("flop_leads_won".to_string());
+        for num_winners in 1..=MAX_PLAYERS {
+            column_names.push(format!("pots_won_by_{}", num_winners));
+        }
+        column_names.push("detailed_hands".to_string());
+        column_names.push("legacy_pot_share".to_string());
+        return column_names;
+    }
+
+    pub fn get(&self, stat: Stats) -> u64 {
+        return self.counts[stat.get_index()];
+    }
+
+    /// Records a showdown reached with a hand of `rank`.
+    pub fn record_hand(&mut self, rank: HandRank) {
+        self.counts[Stats::NumberOfHands.get_index()] += 1;
+        self.detailed_hands += 1;
+        self.showdown_categories[rank.get_category()] += 1;
+    }
+
+    /// Records a pot won with a hand of `rank` together with `num_winners - 1` other players.
+    pub fn record_win(&mut self, num_winners: usize, rank: HandRank) {
+        self.win_categories[rank.get_category()] += 1;
+        if num_winners == 1 {
+            self.counts[Stats::NumberOfWins.get_index()] += 1;
+        } else {
+            self.counts[Stats::NumberOfTies.get_index()] += 1;
+        }
+        self.split_pots[num_winners - 1] += 1;
+    }
+
+    /// Records a pot lost with a hand of `rank` to a hand of `winning_rank`.
+    pub fn record_loss(&mut self, rank: HandRank, winning_rank: HandRank) {
+        self.loss_matrix[rank.get_category()][winning_rank.get_category()] += 1;
+    }
+
+    /// Records a showdown finished in `position`, 1 being the pot winners.
+    pub fn record_finish(&mut self, position: usize) {
+        self.finishing_positions[position - 1] += 1;
+    }
+
+    /// Records the hand of `rank` made on `street`, and whether no other seat was ahead.
+    pub fn record_street(&mut self, street: Street, rank: HandRank, is_leading: bool) {
+        self.street_categories[street.get_index()][rank.get_category()] += 1;
+        if is_leading {
+            self.street_leads[street.get_index()] += 1;
+        }
+    }
+
+    /// Records a pot won or chopped after leading on the flop.
+    pub fn record_flop_lead_won(&mut self) {
+        self.flop_leads_won += 1;
+    }
+
+    /// Adds the counts of `other`, `None` if any count overflowed. The stats are left unchanged
+    /// when a count overflows.
+    pub fn combine(&mut self, other: &HandStats) -> Option<()> {
+        let mut combined = self.clone();
+        add_counts(&mut combined.counts, &other.counts)?;
+        combined.legacy_pot_share += other.legacy_pot_share;
+        for (section, other_section) in combined
+            .count_sections_mut()
+            .into_iter()
+            .zip(other.count_sections())
+        {
+            add_counts(section, other_section)?;
+        }
+        *self = combined;
+        return Some(());
+    }
+
+    /// The number of pots won, counting chopped pots by their share.
+    pub fn get_pot_share(&self) -> f64 {
+        return self.split_pots.iter().enumerate().fold(
+            self.legacy_pot_share,
+            |pot_share, (num_other_winners, num_pots)| {
+                pot_share + *num_pots as f64 / (num_other_winners + 1) as f64
+            },
+        );
+    }
+
+    /// The fraction of pots won, counting chopped pots by their share.
+    pub fn get_equity(&self) -> f64 {
+        let num_hands = self.get(Stats::NumberOfHands);
+        if num_hands == 0 {
+            return 0.0;
+        }
+        return self.get_pot_share() / num_hands as f64;
+    }
+
+    /// Standard error of `get_equity`. A hand's pot share is between 0 and 1, so its variance is
+    /// at most p(1 - p) and the binomial standard error is a safe upper bound.
+    pub fn get_standard_error(&self) -> f64 {
+        let num_hands = self.get(Stats::NumberOfHands);
+        if num_hands == 0 {
+            return 0.0;
+        }
+        let equity = self.get_equity();
+        return (equity * (1.0 - equity) / num_hands as f64).sqrt();
+    }
+
+    /// Wilson score 95% interval of `get_equity`, which stays within 0 and 1 and holds up for
+    /// hands that were rarely dealt.
+    pub fn get_confidence_interval(&self) -> (f64, f64) {
+        let num_hands = self.get(Stats::NumberOfHands) as f64;
+        if num_hands == 0.0 {
+            return (0.0, 1.0);
+        }
+        let equity = self.get_equity();
+        let z_squared = Z_95 * Z_95;
+        let denominator = 1.0 + z_squared / num_hands;
+        let centre = (equity + z_squared / (2.0 * num_hands)) / denominator;
+        let half_width = Z_95 / denominator
+            * (equity * (1.0 - equity) / num_hands + z_squared / (4.0 * num_hands * num_hands))
+                .sqrt();
+        return (
+            (centre - half_width).max(0.0),
+            (centre + half_width).min(1.0),
+        );
+    }
+
+    /// Whether the 95% intervals of both hands overlap, so their order could be noise.
+    pub fn overlaps(&self, other: &HandStats) -> bool {
+        let (low, high) = self.get_confidence_interval();
+        let (other_low, other_high) = other.get_confidence_interval();
+        return low <= other_high && other_low <= high;
+    }
+
+    pub fn to_values(&self) -> Vec<String> {
+        let mut values = self
+            .counts
+            .iter()
+            .map(|count| count.to_string())
+            .collect::<Vec<String>>();
+        values.push(self.get_pot_share().to_string());
+        for count in self.count_sections().into_iter().flatten() {
+            values.push(count.to_string());
+        }
+        values.push(self.legacy_pot_share.to_string());
+        return values;
+    }
+
+    /// Number of values in rows written before pots were counted by their number of winners.
+    pub fn num_values_before_split_pots() -> usize {
+        return NUM_STATS
+            + 1
+            + HandStats::new().count_sections()[..NUM_SECTIONS_BEFORE_SPLIT_POTS]
+                .iter()
+                .map(|section| section.len())
+                .sum::<usize>();
+    }
+
+    /// Parses the values written by `to_values`. Legacy rows only hold hands and wins, with
+    /// chopped pots counted as wins, so their wins are used as the pot share. Rows written before
+    /// a section of counts was added leave that section and the ones after it at 0. Rows written
+    /// before pots were counted by their number of winners keep their pot share as the legacy
+    /// pot share, later rows end with the legacy pot share instead. Their detailed hands are the
+    /// hands counted by showdown category, as every showdown was counted since categories were.
+    pub fn from_values(values: &[&str]) -> Option<Self> {
+        let mut hand_stats = HandStats::new();
+        if values.len() == NUM_LEGACY_STATS {
+            parse_counts(&mut hand_stats.counts[..NUM_LEGACY_STATS], values)?;
+            hand_stats.legacy_pot_share = hand_stats.get(Stats::NumberOfWins) as f64;
+            return Some(hand_stats);
+        }
+
+        if values.len() < NUM_STATS + 1 {
+            return None;
+        }
+        let (stat_values, mut section_values) = values.split_at(NUM_STATS + 1);
+        parse_counts(&mut hand_stats.counts, &stat_values[..NUM_STATS])?;
+        let has_split_pots = values.len() > HandStats::num_values_before_split_pots();
+        let legacy_pot_share_value = match has_split_pots {
+            true => {
+                let (last_value, other_values) = section_values.split_last()?;
+                section_values = other_values;
+                last_value
+            }
+            false => &stat_values[NUM_STATS],
+        };
+        hand_stats.legacy_pot_share = legacy_pot_share_value.trim().parse().ok()?;
+
+        for section in hand_stats.count_sections_mut() {
+            if section_values.is_empty() {
+                break;
+            }
+            if section_values.len() < section.len() {
+                return None;
+            }
+            let (values, remaining_values) = section_values.split_at(section.len());
+            parse_counts(section, values)?;
+            section_values = remaining_values;
+        }
+        if !section_values.is_empty() {
+            return None;
+        }
+        if !has_split_pots {
+            hand_stats.detailed_hands = hand_stats.showdown_categories.iter().sum();
+        }
+        return Some(hand_stats);
+    }
+}
+
+fn add_counts(counts: &mut [u64], other_counts: &[u64]) -> Option<()> {
+    for (count, other_count) in counts.iter_mut().zip(other_counts) {
+        *count = count.checked_add(*other_count)?;
+    }
+    return Some(());
+}
+
+/// Parses one count from every value, `None` if any of them is not a count.
+fn parse_counts(counts: &mut [u64], values: &[&str]) -> Option<()> {
+    for (count, value) in counts.iter_mut().zip(values) {
+        *count = value.trim().parse().ok()?;
+    }
+    return Some(());
+}
+
+/// Stats of every starting hand, or of every two card combination when verbose, indexed by
+/// `get_hand_index` so that recording a deal never builds or hashes a hand's name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HandStatsTable {
+    verbose: bool,
+    hands: Vec<HandStats>,
+}
+
+impl HandStatsTable {
+    pub fn new(verbose: bool) -> Self {
+        let num_hands = match verbose {
+            true => NUM_HOLE_CARD_COMBOS,
+            false => NUM_STARTING_HANDS,
+        };
+        Self {
+            verbose,
+            hands: vec![HandStats::new(); num_hands],
+        }
+    }
+
+    pub fn get_hand_index(&self, cards: &[Card]) -> usize {
+        return match self.verbose {
+            true => Card::get_combo_index(cards),
+            false => StartingHand::from_cards(cards).get_index(),
+        };
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut HandStats {
+        return &mut self.hands[index];
+    }
+
+    /// The hand's name as written to the output file.
+    pub fn get_hand_name(&self, index: usize) -> String {
+        return match self.verbose {
+            true => Card::cards_to_single_string(&Card::combo_from_index(index)),
+            false => Card::get_shorten_hand_string(&StartingHand::from_index(index).combos()[0]),
+        };
+    }
+
+    /// The index of every name a hand can have in an output file. Verbose files written before
+    /// hands were indexed may hold a pair's cards in either order.
+    pub fn get_name_indices(&self) -> HashMap<String, usize> {
+        let mut name_indices = HashMap::new();
+        for index in 0..self.hands.len() {
+            name_indices.insert(self.get_hand_name(index), index);
+            if self.verbose {
+                let mut cards = Card::combo_from_index(index);
+                cards.reverse();
+                name_indices.insert(Card::cards_to_single_string(&cards), index);
+            }
+        }
+        return name_indices;
+    }
+
+    /// Adds every hand's counts from `other`, which must have the same verbosity. Hands before
+    /// the first one to overflow keep the added counts.
+    pub fn combine(&mut self, other: &HandStatsTable) -> Result<(), CountOverflowError> {
+        for index in 0..self.hands.len() {
+            self.hands[index]
+                .combine(&other.hands[index])
+                .ok_or_else(|| CountOverflowError(self.get_hand_name(index)))?;
+        }
+        return Ok(());
+    }
+
+    /// Every hand dealt at least once with its name, sorted by name.
+    pub fn dealt_hands(&self) -> Vec<(String, &HandStats)> {
+        let mut dealt_hands = self
+            .hands
+            .iter()
+            .enumerate()
+            .filter(|(_, stats)| stats.get(Stats::NumberOfHands) > 0)
+            .map(|(index, stats)| (self.get_hand_name(index), stats))
+            .collect::<Vec<(String, &HandStats)>>();
+        dealt_hands.sort_by(|(hand_1, _), (hand_2, _)| hand_1.cmp(hand_2));
+        return dealt_hands;
+    }
+
+    pub fn num_dealt_hands(&self) -> usize {
+        return self
+            .hands
+            .iter()
+            .filter(|stats| stats.get(Stats::NumberOfHands) > 0)
+            .count();
+    }
+
+    /// Every dealt hand from the highest equity to the lowest, hands with equal equity by name.
+    pub fn rank_hands(&self) -> Vec<(String, &HandStats)> {
+        let mut ranked_hands = self.dealt_hands();
+        ranked_hands.sort_by(|(hand_1, stats_1), (hand_2, stats_2)| {
+            stats_2
+                .get_equity()
+                .total_cmp(&stats_1.get_equity())
+                .then(hand_1.cmp(hand_2))
+        });
+        return ranked_hands;
+    }
+
+    /// The largest standard error of any hand, infinite until every hand was dealt.
+    pub fn get_largest_standard_error(&self) -> f64 {
+        if self.num_dealt_hands() < self.hands.len() {
+            return f64::INFINITY;
+        }
+        return self
+            .hands
+            .iter()
+            .map(|stats| stats.get_standard_error())
+            .fold(0.0, f64::max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Card;
+
+    fn hand_stats(num_hands: u64, pot_share: f64) -> HandStats {
+        let mut hand_stats = HandStats::new();
+        hand_stats.counts[Stats::NumberOfHands.get_index()] = num_hands;
+        hand_stats.legacy_pot_share = pot_share;
+        return hand_stats;
+    }
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-4, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn values_round_trip_and_older_rows_still_parse() {
+        let pair = HandRank::evaluate(&Card::parse_cards("AhAd9c8s2h4d6c").unwrap());
+        let flush = HandRank::evaluate(&Card::parse_cards("Ah9h7h5h3h2hKc").unwrap());
+        let mut hand_stats = HandStats::new();
+        hand_stats.record_hand(pair);
+        hand_stats.record_hand(flush);
+        hand_stats.record_win(2, flush);
+        hand_stats.record_loss(pair, flush);
+        hand_stats.record_finish(1);
+        hand_stats.record_finish(3);
+        hand_stats.record_street(Street::Flop, pair, true);
+        hand_stats.record_street(Street::Turn, pair, false);
+        hand_stats.record_flop_lead_won();
+
+        let values = hand_stats.to_values();
+        let parsed =
+            HandStats::from_values(&values.iter().map(String::as_str).collect::<Vec<&str>>())
+                .unwrap();
+        assert_eq!(parsed.counts, hand_stats.counts);
+        assert_eq!(parsed.get_pot_share(), 0.5);
+        assert_eq!(parsed, hand_stats);
+        assert_eq!(parsed.showdown_categories, hand_stats.showdown_categories);
+        assert_eq!(parsed.win_categories[flush.get_category()], 1);
+        assert_eq!(
+            parsed.loss_matrix[pair.get_category()][flush.get_category()],
+            1
+        );
+
+        let older_row = HandStats::from_values(&["10", "3", "1", "3.5"]).unwrap();
+        assert_eq!(older_row.showdown_categories, [0; NUM_HAND_CATEGORIES]);
+        assert_eq!(older_row.detailed_hands, 0);
+        let legacy_row = HandStats::from_values(&["10", "3"]).unwrap();
+        assert_eq!(legacy_row.get_pot_share(), 3.0);
+        let mut mixed_rows = parsed.clone();
+        mixed_rows.combine(&legacy_row).unwrap();
+        assert_eq!(mixed_rows.get(Stats::NumberOfHands), 12);
+        assert_eq!(mixed_rows.detailed_hands, 2);
+        assert!(HandStats::from_values(&["10", "3", "1", "3.5", "1"]).is_none());
+
+        let mut category_values = vec!["1"; NUM_STATS + 1 + 2 * NUM_HAND_CATEGORIES];
+        let category_row = HandStats::from_values(&category_values).unwrap();
+        assert_eq!(category_row.win_categories, [1; NUM_HAND_CATEGORIES]);
+        assert_eq!(category_row.detailed_hands, NUM_HAND_CATEGORIES as u64);
+        assert_eq!(category_row.loss_matrix[0], [0; NUM_HAND_CATEGORIES]);
+        category_values.push("1");
+        assert!(HandStats::from_values(&category_values).is_none());
+    }
+
+    #[test]
+    fn every_value_has_a_unique_column_name() {
+        let column_names = HandStats::get_column_names();
+        assert_eq!(column_names.len(), HandStats::new().to_values().len());
+        assert_eq!(
+            column_names
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            column_names.len()
+        );
+        assert_eq!(column_names[4], "showdown_high_card");
+        let flop_leads_won = column_names.len() - MAX_PLAYERS as usize - 3;
+        assert_eq!(column_names[flop_leads_won - 1], "turn_leads");
+        assert_eq!(column_names[flop_leads_won + 1], "pots_won_by_1");
+        assert_eq!(column_names.last().unwrap(), "legacy_pot_share");
+    }
+
+    #[test]
+    fn combined_tables_do_not_depend_on_how_deals_were_split() {
+        let winning_rank = HandRank::evaluate(&Card::parse_cards("AhAd9c8s2h4d6c").unwrap());
+        let mut whole_table = HandStatsTable::new(false);
+        let mut part_tables = vec![HandStatsTable::new(false); 3];
+        let aces = whole_table.get_hand_index(&Card::parse_cards("AhAd").unwrap());
+        for deal in 0..1000 {
+            let num_winners = deal % 7 + 1;
+            let part_table = &mut part_tables[deal % 3];
+            for table in [&mut whole_table, part_table] {
+                let stats = table.get_mut(aces);
+                stats.record_hand(winning_rank);
+                stats.record_win(num_winners, winning_rank);
+            }
+        }
+
+        let mut combined_table = HandStatsTable::new(false);
+        for part_table in part_tables.iter().rev() {
+            combined_table.combine(part_table).unwrap();
+        }
+        assert_eq!(combined_table, whole_table);
+        assert_eq!(
+            combined_table.get_mut(aces).to_values(),
+            whole_table.get_mut(aces).to_values()
+        );
+    }
+
+    #[test]
+    fn combining_refuses_overflowed_counts() {
+        let near_max =
+            HandStats::from_values(&["18446744073709551615", "4294967296", "0", "1.5"]).unwrap();
+        let mut combined = hand_stats(1, 0.5);
+        assert!(combined.combine(&near_max).is_none());
+        assert_eq!(combined.get(Stats::NumberOfHands), 1);
+        assert_eq!(combined.get_pot_share(), 0.5);
+
+        let mut table = HandStatsTable::new(false);
+        let mut other_table = HandStatsTable::new(false);
+        let aces = table.get_hand_index(&Card::parse_cards("AhAd").unwrap());
+        table.get_mut(aces).combine(&near_max).unwrap();
+        other_table
+            .get_mut(aces)
+            .combine(&hand_stats(1, 1.0))
+            .unwrap();
+        assert_eq!(
+            table.combine(&other_table),
+            Err(CountOverflowError("A | A Off Suit".to_string()))
+        );
+    }
+
+    #[test]
+    fn wilson_interval_matches_known_values() {
+        let (low, high) = hand_stats(100, 50.0).get_confidence_interval();
+        assert_close(low, 0.4038);
+        assert_close(high, 0.5962);
+
+        let (low, high) = hand_stats(10, 0.0).get_confidence_interval();
+        assert_close(low, 0.0);
+        assert_close(high, 0.2775);
+
+        assert_eq!(hand_stats(0, 0.0).get_confidence_interval(), (0.0, 1.0));
+    }
+
+    #[test]
+    fn standard_error_shrinks_with_more_hands() {
+        assert_close(hand_stats(100, 50.0).get_standard_error(), 0.05);
+        assert_close(hand_stats(10_000, 5_000.0).get_standard_error(), 0.005);
+    }
+
+    #[test]
+    fn flags_overlapping_intervals() {
+        assert!(hand_stats(100, 50.0).overlaps(&hand_stats(100, 45.0)));
+        assert!(!hand_stats(100_000, 50_000.0).overlaps(&hand_stats(100_000, 45_000.0)));
+    }
+}